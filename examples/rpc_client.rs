@@ -11,6 +11,7 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
+use std::time;
 use uprotocol_sdk::{
     rpc::RpcClient,
     transport::builder::UAttributesBuilder,
@@ -56,6 +57,12 @@ async fn main() {
         data: Some(Data::Value(data.as_bytes().to_vec())),
     };
 
+    // wait for the RPC server
+    rpc_client
+        .wait_for_rpc_server(&uuri, time::Duration::from_secs(10))
+        .await
+        .unwrap();
+
     // invoke RPC method
     println!("Send request to {}", uuri.to_string());
     let result = rpc_client.invoke_method(uuri, payload, attributes).await;
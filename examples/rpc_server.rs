@@ -11,25 +11,17 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use async_std::task::{self, block_on};
 use chrono::Utc;
-use std::sync::{Arc, Mutex};
-use std::time;
 use uprotocol_sdk::{
-    rpc::RpcServer,
-    transport::datamodel::UTransport,
-    uprotocol::{Data, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat, UStatus, UUri},
+    uprotocol::{Data, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat, UUri},
     uri::builder::resourcebuilder::UResourceBuilder,
 };
-use uprotocol_zenoh_rust::ULinkZenoh;
+use uprotocol_zenoh_rust::blocking::ULinkZenoh;
 use zenoh::config::Config;
 
-#[async_std::main]
-async fn main() {
+fn main() {
     println!("uProtocol RPC server example");
-    let rpc_server = Arc::new(Mutex::new(
-        ULinkZenoh::new(Config::default()).await.unwrap(),
-    ));
+    let rpc_server = ULinkZenoh::new(Config::default()).unwrap();
 
     // create uuri
     // TODO: Need to check whether the way to create ID is correct?
@@ -47,8 +39,11 @@ async fn main() {
         ..Default::default()
     };
 
-    let rpc_server_cloned = rpc_server.clone();
-    let callback = move |result: Result<UMessage, UStatus>| {
+    println!("Register the listener...");
+    let requests = rpc_server.serve(uuri).unwrap();
+
+    // Requests are polled from the main thread, no need to block inside the Zenoh callback
+    for result in requests.iter() {
         match result {
             Ok(msg) => {
                 let UMessage {
@@ -73,29 +68,11 @@ async fn main() {
                 let mut uattributes = attributes.unwrap();
                 uattributes.set_type(UMessageType::UmessageTypeResponse);
                 // Send back result
-                block_on(
-                    rpc_server_cloned
-                        .lock()
-                        .unwrap()
-                        .send(uuri, upayload, uattributes),
-                )
-                .unwrap();
+                rpc_server.send(uuri, upayload, uattributes).unwrap();
             }
             Err(ustatus) => {
                 println!("Internal Error: {:?}", ustatus);
             }
         }
-    };
-
-    println!("Register the listener...");
-    rpc_server
-        .lock()
-        .unwrap()
-        .register_rpc_listener(uuri, Box::new(callback))
-        .await
-        .unwrap();
-
-    loop {
-        task::sleep(time::Duration::from_millis(1000)).await;
     }
 }
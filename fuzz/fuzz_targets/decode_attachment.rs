@@ -0,0 +1,26 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use uprotocol_zenoh_rust::codec;
+use zenoh::sample::AttachmentBuilder;
+
+// Attachments with any items, including a `uattributes` one
+fuzz_target!(|items: Vec<(&[u8], &[u8])>| {
+    let mut attachment = AttachmentBuilder::new();
+    for (name, value) in items {
+        attachment.insert(name, value);
+    }
+    let _ = codec::attachment_attributes(Some(&attachment.build()));
+});
@@ -0,0 +1,24 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use uprotocol_zenoh_rust::codec;
+
+// Any decoded UAttributes is encoded back losslessly
+fuzz_target!(|buf: &[u8]| {
+    if let Ok(attributes) = codec::decode_attributes(buf) {
+        let encoded = codec::encode_attributes(&attributes);
+        assert_eq!(codec::decode_attributes(&encoded), Ok(attributes));
+    }
+});
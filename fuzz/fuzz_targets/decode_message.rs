@@ -0,0 +1,29 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+use uprotocol_zenoh_rust::codec;
+use zenoh::prelude::Encoding;
+
+// The raw attributes, encoding and payload of a sample or a query
+fuzz_target!(|parts: (&[u8], &str, &[u8])| {
+    let (attributes, encoding, payload) = parts;
+    let encoding = Encoding::from(encoding.to_string());
+    if let Ok((_, decoded)) = codec::decode_message(attributes, &encoding, payload) {
+        assert_eq!(
+            codec::decode_format(&codec::encoding(decoded.format)),
+            Ok(decoded.format)
+        );
+    }
+});
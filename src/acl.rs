@@ -0,0 +1,303 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Access control of the local uEntities, enforced by the uLink whatever the Zenoh router ACLs.
+//!
+//! The policy is JSON, granting each uEntity, by id, the `UUri`s it may publish to, subscribe
+//! to, invoke and serve:
+//! ```json
+//! {
+//!   "entities": [
+//!     {
+//!       "entity": 1234,
+//!       "publish": ["1234/1/*"],
+//!       "subscribe": ["*/*/*"],
+//!       "invoke": ["4321/1/1"],
+//!       "serve": ["1234/1/*"]
+//!     }
+//!   ]
+//! }
+//! ```
+//! The `UUri`s are in the forms of [`crate::uri`], `*` matching any id. Whatever isn't granted is
+//! denied, so a uEntity missing from the policy can't do anything.
+//!
+//! An [`AccessControl`] applies the policy of the uEntity a uLink acts for: `send` and
+//! `enable_publication_cache` check the publications (`publish`), `send` the responses (`serve`),
+//! `invoke_method` the requests (`invoke`), `register_listener`, `get_latest` and the storages
+//! the topics (`subscribe`), and `register_rpc_listener` the methods (`serve`). Each decision is
+//! passed to the audit listener as an [`AuditEvent`].
+use crate::uri;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use uprotocol_sdk::uprotocol::{UCode, UEntity, UStatus, UUri};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    entities: Vec<PolicyEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyEntry {
+    entity: u32,
+    #[serde(default)]
+    publish: Vec<String>,
+    #[serde(default)]
+    subscribe: Vec<String>,
+    #[serde(default)]
+    invoke: Vec<String>,
+    #[serde(default)]
+    serve: Vec<String>,
+}
+
+/// What a uEntity does with a `UUri`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Send publications or notifications to the topic
+    Publish,
+    /// Listen to the topic
+    Subscribe,
+    /// Send requests to the RPC method
+    Invoke,
+    /// Listen to the requests of the RPC method, and respond to them
+    Serve,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Action::Publish => "publish",
+            Action::Subscribe => "subscribe",
+            Action::Invoke => "invoke",
+            Action::Serve => "serve",
+        };
+        f.write_str(action)
+    }
+}
+
+// The UUri patterns granted to a uEntity, by action
+#[derive(Clone, Debug, Default)]
+struct Grants {
+    publish: Vec<UUri>,
+    subscribe: Vec<UUri>,
+    invoke: Vec<UUri>,
+    serve: Vec<UUri>,
+}
+
+impl Grants {
+    fn from_entry(entry: &PolicyEntry) -> Result<Grants, UStatus> {
+        let parse = |uris: &[String]| {
+            uris.iter()
+                .map(|uri| {
+                    uri::parse_uri(uri)
+                        .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Grants {
+            publish: parse(&entry.publish)?,
+            subscribe: parse(&entry.subscribe)?,
+            invoke: parse(&entry.invoke)?,
+            serve: parse(&entry.serve)?,
+        })
+    }
+
+    fn patterns(&self, action: Action) -> &[UUri] {
+        match action {
+            Action::Publish => &self.publish,
+            Action::Subscribe => &self.subscribe,
+            Action::Invoke => &self.invoke,
+            Action::Serve => &self.serve,
+        }
+    }
+}
+
+/// The `UUri`s each uEntity may publish to, subscribe to, invoke and serve
+#[derive(Clone, Debug, Default)]
+pub struct AccessPolicy {
+    // Grants by entity id
+    entities: HashMap<u32, Grants>,
+}
+
+impl AccessPolicy {
+    /// # Errors
+    /// Will return `Err` if the JSON isn't a valid policy
+    pub fn from_json(json: &str) -> Result<AccessPolicy, UStatus> {
+        let file: PolicyFile = serde_json::from_str(json)
+            .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e.to_string()))?;
+        let entities = file
+            .entities
+            .iter()
+            .map(|entry| Grants::from_entry(entry).map(|grants| (entry.entity, grants)))
+            .collect::<Result<_, UStatus>>()?;
+        Ok(AccessPolicy { entities })
+    }
+
+    /// # Errors
+    /// Will return `Err` if unable to read the file, or if it isn't a valid policy
+    pub fn from_file(path: impl AsRef<Path>) -> Result<AccessPolicy, UStatus> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e.to_string()))?;
+        AccessPolicy::from_json(&json)
+    }
+
+    /// Whether the policy lets the uEntity do the action with the `UUri`.
+    /// A pattern, e.g. of `register_pattern_listener`, needs to be covered by a granted one.
+    #[must_use]
+    pub fn is_allowed(&self, entity: u32, action: Action, uri: &UUri) -> bool {
+        self.entities.get(&entity).is_some_and(|grants| {
+            grants
+                .patterns(action)
+                .iter()
+                .any(|pattern| uri::matches(pattern, uri))
+        })
+    }
+}
+
+/// An access decision of the uLink
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEvent {
+    /// Id of the uEntity the uLink acts for
+    pub entity: u32,
+    pub action: Action,
+    pub uri: UUri,
+    pub allowed: bool,
+}
+
+/// The access policy applied to the uEntity a uLink acts for
+pub struct AccessControl {
+    policy: AccessPolicy,
+    entity: u32,
+    audit: Option<Box<dyn Fn(&AuditEvent) + Send + Sync + 'static>>,
+}
+
+impl AccessControl {
+    /// # Errors
+    /// Will return `Err` if the uEntity has no id
+    pub fn new(policy: AccessPolicy, entity: &UEntity) -> Result<AccessControl, UStatus> {
+        let entity = entity.id.ok_or_else(|| {
+            UStatus::fail_with_code(UCode::InvalidArgument, "The uEntity needs an id")
+        })?;
+        Ok(AccessControl {
+            policy,
+            entity,
+            audit: None,
+        })
+    }
+
+    /// Pass every access decision to the listener, e.g. to keep an audit trail
+    #[must_use]
+    pub fn with_audit(
+        mut self,
+        audit: Box<dyn Fn(&AuditEvent) + Send + Sync + 'static>,
+    ) -> AccessControl {
+        self.audit = Some(audit);
+        self
+    }
+
+    /// Check that the uEntity may do the action with the `UUri`, and audit the decision
+    ///
+    /// # Errors
+    /// Will return `Err` with `PermissionDenied` if the policy doesn't allow it
+    pub fn check(&self, action: Action, uri: &UUri) -> Result<(), UStatus> {
+        let allowed = self.policy.is_allowed(self.entity, action, uri);
+        #[cfg(feature = "tracing")]
+        if allowed {
+            tracing::debug!(entity = self.entity, %action, uuri = ?uri, "Access allowed");
+        } else {
+            tracing::warn!(entity = self.entity, %action, uuri = ?uri, "Access denied");
+        }
+        if let Some(audit) = &self.audit {
+            audit(&AuditEvent {
+                entity: self.entity,
+                action,
+                uri: uri.clone(),
+                allowed,
+            });
+        }
+        if allowed {
+            Ok(())
+        } else {
+            Err(UStatus::fail_with_code(
+                UCode::PermissionDenied,
+                &format!(
+                    "uEntity {} isn't allowed to {action} this UUri",
+                    self.entity
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    const POLICY: &str = r#"{
+        "entities": [
+            { "entity": 1234, "publish": ["1234/1/*"], "invoke": ["4321/1/1"] }
+        ]
+    }"#;
+
+    fn uri(uri: &str) -> UUri {
+        uri::parse_uri(uri).unwrap()
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let policy = AccessPolicy::from_json(POLICY).unwrap();
+        assert!(policy.is_allowed(1234, Action::Publish, &uri("1234/1/5678")));
+        assert!(!policy.is_allowed(1234, Action::Publish, &uri("1234/2/5678")));
+        assert!(!policy.is_allowed(1234, Action::Subscribe, &uri("1234/1/5678")));
+        assert!(policy.is_allowed(1234, Action::Invoke, &uri("4321/1/1")));
+        assert!(!policy.is_allowed(1234, Action::Invoke, &uri("4321/1/*")));
+        assert!(!policy.is_allowed(4321, Action::Publish, &uri("1234/1/5678")));
+
+        assert!(
+            AccessPolicy::from_json(r#"{ "entities": [{ "entity": 1, "read": [] }] }"#).is_err()
+        );
+        assert!(AccessPolicy::from_json(
+            r#"{ "entities": [{ "entity": 1, "publish": ["1/1"] }] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_check_audit() {
+        let events = Arc::new(Mutex::new(vec![]));
+        let events_cloned = events.clone();
+        let entity = UEntity {
+            name: "body.access".to_string(),
+            id: Some(1234),
+            ..Default::default()
+        };
+        let access = AccessControl::new(AccessPolicy::from_json(POLICY).unwrap(), &entity)
+            .unwrap()
+            .with_audit(Box::new(move |event| {
+                events_cloned.lock().unwrap().push(event.clone());
+            }));
+        assert!(access.check(Action::Publish, &uri("1234/1/5678")).is_ok());
+        let status = access.check(Action::Serve, &uri("1234/1/1")).unwrap_err();
+        assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].allowed);
+        assert_eq!(events[1].action, Action::Serve);
+        assert!(!events[1].allowed);
+    }
+}
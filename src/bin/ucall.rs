@@ -0,0 +1,99 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::time::Instant;
+use uprotocol_sdk::{
+    transport::builder::UAttributesBuilder,
+    uprotocol::{Data, UCode, UPayload, UPriority, UStatus},
+    uuid::builder::UUIDv8Builder,
+};
+use uprotocol_zenoh_rust::{
+    cli::{self, OutputFormat, PayloadArgs, ProtoArgs, UriArgs, ZenohArgs},
+    rt, ULinkZenoh,
+};
+
+/// Invoke a uProtocol RPC method and print its response
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    #[command(flatten)]
+    uri: UriArgs,
+    #[command(flatten)]
+    payload: PayloadArgs,
+    #[command(flatten)]
+    proto: ProtoArgs,
+    /// Priority, from cs0 to cs6
+    #[arg(short, long, default_value = "cs4", value_parser = cli::parse_priority)]
+    priority: UPriority,
+    /// Time to live of the request, and how long its response is waited for, in milliseconds
+    #[arg(short, long, default_value_t = 1000)]
+    ttl: i32,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let descriptor = args.proto.message_descriptor()?;
+    let method = args.uri.uri();
+    // A request may have no payload
+    let payload = args.payload.payload(&args.proto)?.unwrap_or(UPayload {
+        length: Some(0),
+        data: Some(Data::Value(vec![])),
+        ..Default::default()
+    });
+
+    let ulink = rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?;
+    let attributes = UAttributesBuilder::request(args.priority, method.clone(), args.ttl)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let start = Instant::now();
+    let result = rt::block_on(ulink.invoke_method_message(method.clone(), payload, attributes));
+    let elapsed = start.elapsed();
+    let response = result.map_err(|e| UStatus {
+        message: Some(format!(
+            "Unable to call {}: {}",
+            cli::format_uri(&method),
+            e.message.as_deref().unwrap_or_default()
+        )),
+        ..e
+    })?;
+
+    match args.output {
+        OutputFormat::Human => {
+            println!("{}", args.output.format(&response, descriptor.as_ref()));
+            eprintln!("Response received in {elapsed:?}");
+        }
+        OutputFormat::Json => {
+            let mut description = cli::message_to_json(&response, descriptor.as_ref());
+            description["latency_ms"] = serde_json::json!(elapsed.as_secs_f64() * 1000.0);
+            println!("{description}");
+        }
+    }
+
+    // Exit with the failure of the method
+    match response
+        .attributes
+        .and_then(|attributes| attributes.commstatus)
+    {
+        Some(code) if code != UCode::Ok as i32 => Err(UStatus {
+            code,
+            message: Some(format!("{} failed", cli::format_uri(&method))),
+            ..Default::default()
+        }),
+        _ => Ok(()),
+    }
+}
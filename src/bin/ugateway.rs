@@ -0,0 +1,42 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use uprotocol_sdk::uprotocol::{UCode, UStatus};
+use uprotocol_zenoh_rust::{cli::ZenohArgs, gateway, rt, ULinkZenoh};
+
+/// Publish and receive uProtocol messages as CloudEvents over HTTP
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    /// Address of the HTTP server
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    http: SocketAddr,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let config = args.zenoh.config()?;
+    rt::block_on(async {
+        let ulink = Arc::new(ULinkZenoh::new(config).await?);
+        eprintln!("Serving CloudEvents on http://{}/events", args.http);
+        axum::Server::try_bind(&args.http)
+            .map_err(|e| UStatus::fail_with_code(UCode::Unavailable, &e.to_string()))?
+            .serve(gateway::router(ulink).into_make_service())
+            .await
+            .map_err(|e| UStatus::fail_with_code(UCode::Internal, &e.to_string()))
+    })
+}
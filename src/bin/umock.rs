@@ -0,0 +1,47 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::path::PathBuf;
+use std::sync::Arc;
+use uprotocol_sdk::uprotocol::UStatus;
+use uprotocol_zenoh_rust::{
+    cli::{self, ZenohArgs},
+    mock::{MockConfig, MockServer},
+    rt, ULinkZenoh,
+};
+
+/// Serve uProtocol RPC methods with the canned responses of a mock file
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    /// JSON file declaring the methods and their responses
+    file: PathBuf,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let config = MockConfig::from_file(&args.file)?;
+
+    let ulink = Arc::new(rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?);
+    for method in &config.methods {
+        eprintln!("Serving {}", cli::format_uri(&method.method));
+    }
+    let _server = rt::block_on(MockServer::start(&ulink, config))?;
+
+    // Serve until killed
+    loop {
+        std::thread::park();
+    }
+}
@@ -0,0 +1,74 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::time::Duration;
+use uprotocol_sdk::{
+    transport::{builder::UAttributesBuilder, datamodel::UTransport},
+    uprotocol::{UCode, UPriority, UStatus},
+};
+use uprotocol_zenoh_rust::{
+    cli::{self, PayloadArgs, ProtoArgs, UriArgs, ZenohArgs},
+    rt, ULinkZenoh,
+};
+
+/// Publish uProtocol messages on a topic
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    #[command(flatten)]
+    uri: UriArgs,
+    #[command(flatten)]
+    payload: PayloadArgs,
+    #[command(flatten)]
+    proto: ProtoArgs,
+    /// Priority, from cs0 to cs6
+    #[arg(short, long, default_value = "cs1", value_parser = cli::parse_priority)]
+    priority: UPriority,
+    /// Time to live, in milliseconds
+    #[arg(short, long)]
+    ttl: Option<i32>,
+    /// Number of messages to publish
+    #[arg(short = 'n', long, default_value_t = 1)]
+    count: u64,
+    /// Interval between the messages, in milliseconds
+    #[arg(short, long, default_value_t = 1000)]
+    interval: u64,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let topic = args.uri.uri();
+    let payload = args.payload.payload(&args.proto)?.ok_or_else(|| {
+        UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "No payload given, use --text, --hex, --file or --json",
+        )
+    })?;
+
+    let ulink = rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?;
+    for i in 0..args.count {
+        if i > 0 {
+            std::thread::sleep(Duration::from_millis(args.interval));
+        }
+        // Every message gets its own id
+        let mut builder = UAttributesBuilder::publish(args.priority);
+        if let Some(ttl) = args.ttl {
+            builder = builder.with_ttl(ttl);
+        }
+        rt::block_on(ulink.send(topic.clone(), payload.clone(), builder.build()))?;
+        eprintln!("Published on {}", cli::format_uri(&topic));
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+use uprotocol_sdk::uprotocol::{UCode, UStatus, UUri};
+use uprotocol_zenoh_rust::{
+    cli::{self, ZenohArgs},
+    record::{self, Record},
+    rt, ULinkZenoh,
+};
+
+/// Record the uProtocol messages of the topics matching the patterns into a file
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    /// Record file to write
+    #[arg(short, long)]
+    output: PathBuf,
+    /// Stop after this number of seconds
+    #[arg(short, long)]
+    duration: Option<u64>,
+    /// Topic patterns, e.g. 1234/1/*
+    #[arg(required = true, value_parser = cli::parse_uri)]
+    topics: Vec<UUri>,
+}
+
+fn io_error(e: &std::io::Error) -> UStatus {
+    UStatus::fail_with_code(UCode::Internal, &e.to_string())
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let mut writer = BufWriter::new(File::create(&args.output).map_err(|e| io_error(&e))?);
+    record::write_header(&mut writer).map_err(|e| io_error(&e))?;
+
+    let ulink = rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?;
+    let (sender, receiver) = mpsc::channel();
+    for topic in &args.topics {
+        let sender = sender.clone();
+        rt::block_on(ulink.register_pattern_listener(
+            topic,
+            Box::new(move |result| {
+                if let Ok(msg) = result {
+                    let _ = sender.send(Record::now(msg));
+                }
+            }),
+        ))?;
+        eprintln!("Recording {}", cli::format_uri(topic));
+    }
+
+    let deadline = args
+        .duration
+        .map(|duration| Instant::now() + Duration::from_secs(duration));
+    let mut count: u64 = 0;
+    loop {
+        let received = match deadline {
+            Some(deadline) => receiver
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok(),
+            None => receiver.recv().ok(),
+        };
+        let Some(record) = received else {
+            break;
+        };
+        record::write_record(&mut writer, &record).map_err(|e| io_error(&e))?;
+        // Flush every message, so that an interrupted recording keeps what was received
+        writer.flush().map_err(|e| io_error(&e))?;
+        count += 1;
+    }
+    eprintln!("{count} messages recorded into {}", args.output.display());
+    Ok(())
+}
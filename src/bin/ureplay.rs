@@ -0,0 +1,113 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use uprotocol_sdk::{
+    transport::datamodel::UTransport,
+    uprotocol::{UCode, UMessageType, UStatus, UUri},
+    uuid::builder::UUIDv8Builder,
+};
+use uprotocol_zenoh_rust::{
+    cli::{self, ZenohArgs},
+    record::{self, RecordReader},
+    rt, ULinkZenoh,
+};
+
+/// Publish again the uProtocol messages of a record file, with their original timing
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    /// Record file to replay
+    input: PathBuf,
+    /// Speed factor of the replay, e.g. 2 to replay twice faster
+    #[arg(short, long, default_value_t = 1.0)]
+    speed: f64,
+    /// Only replay the topics matching these patterns, e.g. 1234/1/*
+    #[arg(short, long, value_parser = cli::parse_uri)]
+    topic: Vec<UUri>,
+    /// Keep the original ids of the messages, instead of new ones starting their TTL again.
+    /// The messages with a TTL are then dropped as expired by the listeners.
+    #[arg(long)]
+    keep_ids: bool,
+}
+
+fn io_error(e: &std::io::Error) -> UStatus {
+    UStatus::fail_with_code(UCode::Internal, &e.to_string())
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    if !args.speed.is_finite() || args.speed <= 0.0 {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The speed should be positive",
+        ));
+    }
+    let mut reader = BufReader::new(File::open(&args.input).map_err(|e| io_error(&e))?);
+    record::read_header(&mut reader).map_err(|e| io_error(&e))?;
+
+    let ulink = rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?;
+    let start = Instant::now();
+    let mut first = None;
+    let (mut count, mut skipped) = (0u64, 0u64);
+    let mut warned = false;
+    for record in RecordReader::new(reader) {
+        let record = record.map_err(|e| io_error(&e))?;
+        let Some(msg) = record.message else {
+            continue;
+        };
+        let (Some(source), Some(mut attributes), Some(payload)) =
+            (msg.source, msg.attributes, msg.payload)
+        else {
+            continue;
+        };
+        if !args.topic.is_empty() && !args.topic.iter().any(|p| cli::matches(p, &source)) {
+            continue;
+        }
+        // The responses can only be sent to the query they answer
+        if attributes.r#type != UMessageType::UmessageTypePublish as i32 {
+            skipped += 1;
+            continue;
+        }
+
+        // Keep the original interval since the first replayed message
+        let first = *first.get_or_insert(record.timestamp);
+        let offset =
+            Duration::from_millis(record.timestamp.saturating_sub(first)).div_f64(args.speed);
+        if let Some(wait) = (start + offset).checked_duration_since(Instant::now()) {
+            std::thread::sleep(wait);
+        }
+        if !args.keep_ids {
+            attributes.id = Some(UUIDv8Builder::new().build());
+        } else if !warned && attributes.ttl.is_some_and(|ttl| ttl > 0) {
+            eprintln!(
+                "The messages with a TTL keep their ids, so they are likely dropped as expired"
+            );
+            warned = true;
+        }
+        match rt::block_on(ulink.send(source.clone(), payload, attributes)) {
+            Ok(()) => count += 1,
+            Err(status) => eprintln!(
+                "Unable to publish on {}: {status:?}",
+                cli::format_uri(&source)
+            ),
+        }
+    }
+    eprintln!("{count} messages replayed, {skipped} non-publish messages skipped");
+    Ok(())
+}
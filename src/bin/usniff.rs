@@ -0,0 +1,74 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::sync::mpsc;
+use uprotocol_sdk::uprotocol::{UCode, UStatus};
+use uprotocol_zenoh_rust::cli::{SniffedMessage, ZenohArgs};
+use zenoh::prelude::sync::*;
+
+/// Print a live table of the uProtocol traffic seen on Zenoh
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    /// Zenoh key expression to sniff
+    #[arg(short, long, default_value = "**")]
+    key: String,
+    /// Also print the queries (RPC requests, cache and storage queries).
+    /// Their replies go straight back to the querier, so they can't be seen.
+    #[arg(short, long)]
+    queries: bool,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let error = |message: &str| UStatus::fail_with_code(UCode::Internal, message);
+    let session = zenoh::open(args.zenoh.config()?)
+        .res()
+        .map_err(|_| error("Unable to open Zenoh session"))?;
+    let key_expr = KeyExpr::try_from(args.key.as_str())
+        .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid key expression"))?;
+
+    let (sender, receiver) = mpsc::channel();
+    let sample_sender = sender.clone();
+    let _subscriber = session
+        .declare_subscriber(&key_expr)
+        .callback(move |sample| {
+            let _ = sample_sender.send(SniffedMessage::from_sample(&sample));
+        })
+        .res()
+        .map_err(|_| error("Unable to declare Zenoh subscriber"))?;
+    // Not complete, so that the RPC requests still go to the servers.
+    // Dropping the query without replying lets the querier finish.
+    let _queryable = if args.queries {
+        Some(
+            session
+                .declare_queryable(&key_expr)
+                .complete(false)
+                .callback(move |query| {
+                    let _ = sender.send(SniffedMessage::from_query(&query));
+                })
+                .res()
+                .map_err(|_| error("Unable to declare Zenoh queryable"))?,
+        )
+    } else {
+        None
+    };
+
+    println!("{}", SniffedMessage::header());
+    for sniffed in receiver {
+        println!("{}", sniffed.row());
+    }
+    Ok(())
+}
@@ -0,0 +1,61 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use clap::Parser;
+use std::sync::mpsc;
+use uprotocol_sdk::uprotocol::UStatus;
+use uprotocol_zenoh_rust::{
+    cli::{self, OutputFormat, ProtoArgs, UriArgs, ZenohArgs},
+    rt, ULinkZenoh,
+};
+
+/// Print the uProtocol messages of a topic, or of the topics matching a pattern
+#[derive(Parser, Debug)]
+struct Args {
+    #[command(flatten)]
+    zenoh: ZenohArgs,
+    #[command(flatten)]
+    uri: UriArgs,
+    #[command(flatten)]
+    proto: ProtoArgs,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Human)]
+    output: OutputFormat,
+    /// Stop after this number of messages
+    #[arg(short = 'n', long)]
+    count: Option<usize>,
+}
+
+fn main() -> Result<(), UStatus> {
+    let args = Args::parse();
+    let descriptor = args.proto.message_descriptor()?;
+    let pattern = args.uri.uri();
+
+    let ulink = rt::block_on(ULinkZenoh::new(args.zenoh.config()?))?;
+    let (sender, receiver) = mpsc::channel();
+    rt::block_on(ulink.register_pattern_listener(
+        &pattern,
+        Box::new(move |result| {
+            let _ = sender.send(result);
+        }),
+    ))?;
+    eprintln!("Listening to {}", cli::format_uri(&pattern));
+
+    for result in receiver.iter().take(args.count.unwrap_or(usize::MAX)) {
+        match result {
+            Ok(msg) => println!("{}", args.output.format(&msg, descriptor.as_ref())),
+            Err(status) => eprintln!("Unable to decode a message: {status:?}"),
+        }
+    }
+    Ok(())
+}
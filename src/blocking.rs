@@ -0,0 +1,237 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Synchronous facade over [`crate::ULinkZenoh`].
+//!
+//! Every call blocks the current thread until it's completed, so the caller doesn't need to run
+//! an executor. Don't use it from inside an async task.
+use crate::{metrics::MetricsSnapshot, rt};
+use std::sync::{
+    mpsc::{self, RecvTimeoutError, TryRecvError},
+    Arc,
+};
+use std::time::Duration;
+use uprotocol_sdk::{
+    rpc::{RpcClient, RpcClientResult, RpcServer},
+    transport::datamodel::UTransport,
+    uprotocol::{UAttributes, UMessage, UPayload, UStatus, UUri},
+};
+use zenoh::config::Config;
+
+pub struct ULinkZenoh {
+    inner: Arc<crate::ULinkZenoh>,
+}
+
+impl ULinkZenoh {
+    /// # Errors
+    /// Will return `Err` if unable to create Zenoh session
+    pub fn new(config: Config) -> Result<ULinkZenoh, UStatus> {
+        Ok(ULinkZenoh {
+            inner: Arc::new(rt::block_on(crate::ULinkZenoh::new(config))?),
+        })
+    }
+
+    /// Get the async uLink wrapped by this facade
+    #[must_use]
+    pub fn as_async(&self) -> &crate::ULinkZenoh {
+        &self.inner
+    }
+
+    #[must_use]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.inner.metrics()
+    }
+
+    /// # Errors
+    /// Same as [`UTransport::send`]
+    pub fn send(
+        &self,
+        topic: UUri,
+        payload: UPayload,
+        attributes: UAttributes,
+    ) -> Result<(), UStatus> {
+        rt::block_on(self.inner.send(topic, payload, attributes))
+    }
+
+    /// # Errors
+    /// Same as [`RpcClient::invoke_method`]
+    pub fn invoke_method(
+        &self,
+        topic: UUri,
+        payload: UPayload,
+        attributes: UAttributes,
+    ) -> RpcClientResult {
+        rt::block_on(self.inner.invoke_method(topic, payload, attributes))
+    }
+
+    /// # Errors
+    /// Same as [`crate::ULinkZenoh::get_latest`]
+    pub fn get_latest(&self, topic: UUri) -> Result<UMessage, UStatus> {
+        rt::block_on(self.inner.get_latest(topic))
+    }
+
+    /// # Errors
+    /// Same as [`crate::ULinkZenoh::wait_for_listener`]
+    pub fn wait_for_listener(&self, topic: &UUri, timeout: Duration) -> Result<(), UStatus> {
+        rt::block_on(self.inner.wait_for_listener(topic, timeout))
+    }
+
+    /// # Errors
+    /// Same as [`crate::ULinkZenoh::wait_for_rpc_server`]
+    pub fn wait_for_rpc_server(&self, method: &UUri, timeout: Duration) -> Result<(), UStatus> {
+        rt::block_on(self.inner.wait_for_rpc_server(method, timeout))
+    }
+
+    /// # Errors
+    /// Same as [`UTransport::register_listener`]
+    pub fn register_listener(
+        &self,
+        topic: UUri,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        rt::block_on(self.inner.register_listener(topic, listener))
+    }
+
+    /// # Errors
+    /// Same as [`UTransport::unregister_listener`]
+    pub fn unregister_listener(&self, topic: UUri, listener: &str) -> Result<(), UStatus> {
+        rt::block_on(self.inner.unregister_listener(topic, listener))
+    }
+
+    /// # Errors
+    /// Same as [`RpcServer::register_rpc_listener`]
+    pub fn register_rpc_listener(
+        &self,
+        method: UUri,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        rt::block_on(self.inner.register_rpc_listener(method, listener))
+    }
+
+    /// # Errors
+    /// Same as [`RpcServer::unregister_rpc_listener`]
+    pub fn unregister_rpc_listener(&self, method: UUri, listener: &str) -> Result<(), UStatus> {
+        rt::block_on(self.inner.unregister_rpc_listener(method, listener))
+    }
+
+    /// Subscribe to the topic, the messages are queued until polled from the returned receiver
+    ///
+    /// # Errors
+    /// Same as [`UTransport::register_listener`]
+    pub fn subscribe(&self, topic: UUri) -> Result<Receiver, UStatus> {
+        let (tx, rx) = mpsc::channel();
+        let listener = self.register_listener(
+            topic.clone(),
+            Box::new(move |result| {
+                // The receiver is dropped only after unregistering
+                let _ = tx.send(result);
+            }),
+        )?;
+        Ok(Receiver {
+            inner: self.inner.clone(),
+            uri: topic,
+            listener,
+            kind: ReceiverKind::Topic,
+            receiver: rx,
+        })
+    }
+
+    /// Serve the RPC method, the requests are queued until polled from the returned receiver
+    ///
+    /// The requests are answered with [`ULinkZenoh::send`], using a response type `UAttributes`.
+    ///
+    /// # Errors
+    /// Same as [`RpcServer::register_rpc_listener`]
+    pub fn serve(&self, method: UUri) -> Result<Receiver, UStatus> {
+        let (tx, rx) = mpsc::channel();
+        let listener = self.register_rpc_listener(
+            method.clone(),
+            Box::new(move |result| {
+                let _ = tx.send(result);
+            }),
+        )?;
+        Ok(Receiver {
+            inner: self.inner.clone(),
+            uri: method,
+            listener,
+            kind: ReceiverKind::Method,
+            receiver: rx,
+        })
+    }
+}
+
+enum ReceiverKind {
+    Topic,
+    Method,
+}
+
+/// Polling receive of the messages of a topic or the requests of an RPC method.
+/// The listener is unregistered when dropped.
+pub struct Receiver {
+    inner: Arc<crate::ULinkZenoh>,
+    uri: UUri,
+    listener: String,
+    kind: ReceiverKind,
+    receiver: mpsc::Receiver<Result<UMessage, UStatus>>,
+}
+
+impl Receiver {
+    /// Block until the next message is received
+    #[must_use]
+    pub fn recv(&self) -> Option<Result<UMessage, UStatus>> {
+        self.receiver.recv().ok()
+    }
+
+    /// Get the next message if there is one already received
+    #[must_use]
+    pub fn try_recv(&self) -> Option<Result<UMessage, UStatus>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Block until the next message is received, or return `None` after the timeout
+    #[must_use]
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<UMessage, UStatus>> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => Some(result),
+            Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Iterate over the received messages, blocking for each of them
+    pub fn iter(&self) -> impl Iterator<Item = Result<UMessage, UStatus>> + '_ {
+        self.receiver.iter()
+    }
+
+    /// The listener string returned by the registration
+    #[must_use]
+    pub fn listener(&self) -> &str {
+        &self.listener
+    }
+}
+
+impl Drop for Receiver {
+    fn drop(&mut self) {
+        let uri = self.uri.clone();
+        let _ = match self.kind {
+            ReceiverKind::Topic => {
+                rt::block_on(self.inner.unregister_listener(uri, &self.listener))
+            }
+            ReceiverKind::Method => {
+                rt::block_on(self.inner.unregister_rpc_listener(uri, &self.listener))
+            }
+        };
+    }
+}
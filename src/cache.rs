@@ -0,0 +1,149 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Publication cache of the topics, for the late joining listeners.
+//!
+//! The last published samples of a topic are kept with their `UAttributes`, and served to the
+//! Zenoh queries on `up/cache/<zenoh key>`.
+use crate::{codec, signing};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use uprotocol_sdk::uprotocol::{UCode, UStatus};
+use zenoh::{
+    prelude::{r#async::AsyncResolve, sync::SyncResolve, *},
+    queryable::{Query, Queryable},
+};
+
+pub(crate) const CACHE_PREFIX: &str = "up/cache";
+
+/// Key queried to get the cached samples of a topic
+pub(crate) fn cache_key(zenoh_key: &str) -> String {
+    format!("{CACHE_PREFIX}/{zenoh_key}")
+}
+
+/// Reply to the query with a kept message, its serialized `UAttributes` and its signature in the
+/// attachment
+pub(crate) fn reply_message(
+    query: &Query,
+    key_expr: KeyExpr<'static>,
+    payload: Vec<u8>,
+    format: i32,
+    attributes: &[u8],
+    signature: Option<&[u8]>,
+) {
+    let value = Value::new(payload.into()).encoding(codec::encoding(format));
+    let mut attachment = codec::attachment(attributes);
+    if let Some(signature) = signature {
+        attachment.insert(signing::SIGNATURE_KEY, signature);
+    }
+    let Ok(reply) = query
+        .reply(Ok(Sample::new(key_expr, value)))
+        .with_attachment(attachment.build())
+    else {
+        return;
+    };
+    let _ = reply.res_sync();
+}
+
+struct CachedSample {
+    payload: Vec<u8>,
+    format: i32,
+    // Serialized UAttributes
+    attributes: Vec<u8>,
+    signature: Option<Vec<u8>>,
+}
+
+pub(crate) struct PublicationCache {
+    history: usize,
+    samples: Arc<Mutex<VecDeque<Arc<CachedSample>>>>,
+    _queryable: Queryable<'static, ()>,
+}
+
+impl PublicationCache {
+    pub(crate) async fn new(
+        session: &Arc<Session>,
+        zenoh_key: &str,
+        history: usize,
+    ) -> Result<PublicationCache, UStatus> {
+        if history == 0 {
+            return Err(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "The history depth should be at least 1",
+            ));
+        }
+        let key_expr = KeyExpr::try_from(cache_key(zenoh_key))
+            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to create Zenoh key"))?;
+        let samples: Arc<Mutex<VecDeque<Arc<CachedSample>>>> =
+            Arc::new(Mutex::new(VecDeque::with_capacity(history)));
+
+        let samples_cloned = samples.clone();
+        let reply_key_expr = key_expr.clone();
+        let queryable = session
+            .declare_queryable(key_expr)
+            .callback(move |query: Query| {
+                // Reply without the lock, so that the publications aren't blocked meanwhile
+                let samples: Vec<_> = samples_cloned.lock().unwrap().iter().cloned().collect();
+                for sample in samples {
+                    reply_message(
+                        &query,
+                        reply_key_expr.clone(),
+                        sample.payload.clone(),
+                        sample.format,
+                        &sample.attributes,
+                        sample.signature.as_deref(),
+                    );
+                }
+            })
+            .res_async()
+            .await
+            .map_err(|_| {
+                UStatus::fail_with_code(UCode::Internal, "Unable to declare cache queryable")
+            })?;
+
+        Ok(PublicationCache {
+            history,
+            samples,
+            _queryable: queryable,
+        })
+    }
+
+    /// Keep the sample, dropping the oldest one once the history depth is reached
+    pub(crate) fn push(
+        &self,
+        payload: Vec<u8>,
+        format: i32,
+        attributes: Vec<u8>,
+        signature: Option<Vec<u8>>,
+    ) {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.len() == self.history {
+            samples.pop_front();
+        }
+        samples.push_back(Arc::new(CachedSample {
+            payload,
+            format,
+            attributes,
+            signature,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cache_key() {
+        assert_eq!(cache_key("0100162e04d20100"), "up/cache/0100162e04d20100");
+    }
+}
@@ -0,0 +1,535 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Helpers shared by the command line tools.
+//!
+//! The tools take the `UUri`s in any of the forms of [`crate::uri`].
+pub use crate::uri::{format_uri, matches, parse_hex, parse_uri};
+use crate::{cloudevent, codec, ULinkZenoh};
+use chrono::{TimeZone, Utc};
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use uprotocol_sdk::uprotocol::{
+    Data, UAttributes, UCode, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat, UPriority,
+    UResource, UStatus, UUri, Uuid,
+};
+use zenoh::{
+    config::{Config, WhatAmI},
+    prelude::{Encoding, Sample},
+    queryable::Query,
+    sample::Attachment,
+};
+
+/// Zenoh session options of the tools
+#[derive(clap::Args, Debug)]
+pub struct ZenohArgs {
+    /// Zenoh configuration file
+    #[arg(short, long)]
+    pub config: Option<PathBuf>,
+    /// Zenoh mode (peer or client)
+    #[arg(short, long)]
+    pub mode: Option<String>,
+    /// Endpoints to connect to, e.g. tcp/192.168.1.1:7447
+    #[arg(short = 'e', long)]
+    pub connect: Vec<String>,
+    /// Endpoints to listen on
+    #[arg(short, long)]
+    pub listen: Vec<String>,
+}
+
+impl ZenohArgs {
+    /// # Errors
+    /// Will return `Err` if the configuration file or an endpoint is invalid
+    pub fn config(&self) -> Result<Config, UStatus> {
+        let invalid = |message: String| UStatus::fail_with_code(UCode::InvalidArgument, &message);
+        let mut config = match &self.config {
+            Some(path) => Config::from_file(path).map_err(|e| invalid(e.to_string()))?,
+            None => Config::default(),
+        };
+        if let Some(mode) = &self.mode {
+            let mode = mode
+                .parse::<WhatAmI>()
+                .map_err(|_| invalid(format!("Invalid Zenoh mode: {mode}")))?;
+            config
+                .set_mode(Some(mode))
+                .map_err(|_| invalid("Unable to set Zenoh mode".to_string()))?;
+        }
+        if !self.connect.is_empty() {
+            config.connect.endpoints = self
+                .connect
+                .iter()
+                .map(|endpoint| endpoint.parse().map_err(|_| invalid(endpoint.clone())))
+                .collect::<Result<_, _>>()?;
+        }
+        if !self.listen.is_empty() {
+            config.listen.endpoints = self
+                .listen
+                .iter()
+                .map(|endpoint| endpoint.parse().map_err(|_| invalid(endpoint.clone())))
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(config)
+    }
+}
+
+/// A `UUri` argument, in any of the forms
+#[derive(clap::Args, Debug)]
+pub struct UriArgs {
+    /// UUri, long (/body.access/1/door.front_left#Door), micro (0100162e04d20100)
+    /// or <entity id>/<major version>/<resource id>
+    #[arg(value_parser = parse_uri)]
+    pub uri: UUri,
+    /// Entity id, which the long form doesn't have
+    #[arg(long)]
+    pub entity_id: Option<u32>,
+    /// Resource id, which the long form doesn't have
+    #[arg(long)]
+    pub resource_id: Option<u32>,
+    /// Entity name, which the other forms may not have but sending needs
+    #[arg(long)]
+    pub entity_name: Option<String>,
+}
+
+impl UriArgs {
+    /// The `UUri`, completed with the ids given apart
+    #[must_use]
+    pub fn uri(&self) -> UUri {
+        let mut uri = self.uri.clone();
+        if let Some(id) = self.entity_id {
+            uri.entity.get_or_insert_with(UEntity::default).id = Some(id);
+        }
+        if let Some(id) = self.resource_id {
+            uri.resource.get_or_insert_with(UResource::default).id = Some(id);
+        }
+        if let Some(name) = &self.entity_name {
+            uri.entity.get_or_insert_with(UEntity::default).name = name.clone();
+        }
+        uri
+    }
+}
+
+/// Parse a priority from `cs0` to `cs6`, to be used as clap `value_parser`
+///
+/// # Errors
+/// Will return `Err` if it isn't a uProtocol priority
+pub fn parse_priority(priority: &str) -> Result<UPriority, String> {
+    UPriority::from_str_name(&format!("UPRIORITY_{}", priority.to_uppercase()))
+        .ok_or_else(|| format!("Invalid priority, expected cs0 to cs6: {priority}"))
+}
+
+/// Protobuf type of the payloads, to convert them from or into JSON
+#[derive(clap::Args, Debug)]
+pub struct ProtoArgs {
+    /// File descriptor set of the protobuf types (protoc --descriptor_set_out)
+    #[arg(long, requires = "proto_message")]
+    pub descriptor: Option<PathBuf>,
+    /// Full name of the protobuf message type, e.g. example.v1.Door
+    #[arg(long, requires = "descriptor")]
+    pub proto_message: Option<String>,
+}
+
+impl ProtoArgs {
+    /// # Errors
+    /// Will return `Err` if unable to read the descriptor set or to find the message type
+    pub fn message_descriptor(&self) -> Result<Option<MessageDescriptor>, UStatus> {
+        let (Some(path), Some(name)) = (&self.descriptor, &self.proto_message) else {
+            return Ok(None);
+        };
+        let invalid = |message: String| UStatus::fail_with_code(UCode::InvalidArgument, &message);
+        let bytes = std::fs::read(path).map_err(|e| invalid(e.to_string()))?;
+        let pool = DescriptorPool::decode(bytes.as_slice()).map_err(|e| invalid(e.to_string()))?;
+        pool.get_message_by_name(name)
+            .map(Some)
+            .ok_or_else(|| invalid(format!("Unknown protobuf message: {name}")))
+    }
+}
+
+/// Payload options of the tools sending messages
+#[derive(clap::Args, Debug)]
+#[group(multiple = false)]
+pub struct PayloadArgs {
+    /// Text payload
+    #[arg(long)]
+    pub text: Option<String>,
+    /// Raw payload, in hexadecimal
+    #[arg(long)]
+    pub hex: Option<String>,
+    /// Raw payload, read from a file
+    #[arg(long)]
+    pub file: Option<PathBuf>,
+    /// JSON payload, encoded into protobuf if --descriptor and --proto-message are given
+    #[arg(long)]
+    pub json: Option<String>,
+}
+
+impl PayloadArgs {
+    /// The payload given, `None` without any payload option
+    ///
+    /// # Errors
+    /// Will return `Err` if the payload is invalid or unable to read it
+    pub fn payload(&self, proto: &ProtoArgs) -> Result<Option<UPayload>, UStatus> {
+        let invalid = |message: String| UStatus::fail_with_code(UCode::InvalidArgument, &message);
+        let (format, data) = if let Some(text) = &self.text {
+            (UPayloadFormat::UpayloadFormatText, text.as_bytes().to_vec())
+        } else if let Some(hex) = &self.hex {
+            (
+                UPayloadFormat::UpayloadFormatRaw,
+                parse_hex(hex).map_err(invalid)?,
+            )
+        } else if let Some(path) = &self.file {
+            (
+                UPayloadFormat::UpayloadFormatRaw,
+                std::fs::read(path).map_err(|e| invalid(e.to_string()))?,
+            )
+        } else if let Some(json) = &self.json {
+            match proto.message_descriptor()? {
+                Some(descriptor) => (
+                    UPayloadFormat::UpayloadFormatProtobuf,
+                    json_to_protobuf(descriptor, json)?,
+                ),
+                None => {
+                    serde_json::from_str::<Value>(json).map_err(|e| invalid(e.to_string()))?;
+                    (UPayloadFormat::UpayloadFormatJson, json.as_bytes().to_vec())
+                }
+            }
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(UPayload {
+            length: Some(0),
+            format: format as i32,
+            data: Some(Data::Value(data)),
+        }))
+    }
+}
+
+/// How the tools print the messages
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum OutputFormat {
+    /// One line per message
+    Human,
+    /// One JSON object per line
+    Json,
+}
+
+impl OutputFormat {
+    #[must_use]
+    pub fn format(self, msg: &UMessage, descriptor: Option<&MessageDescriptor>) -> String {
+        match self {
+            OutputFormat::Human => message_to_human(msg, descriptor),
+            OutputFormat::Json => message_to_json(msg, descriptor).to_string(),
+        }
+    }
+}
+
+/// Encode the JSON into the protobuf message type
+///
+/// # Errors
+/// Will return `Err` if the JSON doesn't match the message type
+pub fn json_to_protobuf(descriptor: MessageDescriptor, json: &str) -> Result<Vec<u8>, UStatus> {
+    let mut deserializer = serde_json::Deserializer::from_str(json);
+    let message = DynamicMessage::deserialize(descriptor, &mut deserializer)
+        .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e.to_string()))?;
+    Ok(prost::Message::encode_to_vec(&message))
+}
+
+/// Format a uProtocol UUID the usual way
+#[must_use]
+pub fn format_uuid(uuid: &Uuid) -> String {
+    cloudevent::uuid_to_string(uuid)
+}
+
+// Name of a protobuf enumeration value, without the prefix of the type
+fn enum_name(name: Option<&str>, prefix: &str) -> String {
+    name.map_or_else(
+        || "unknown".to_string(),
+        |name| name.trim_start_matches(prefix).to_lowercase(),
+    )
+}
+
+/// Describe the message as JSON: source, decoded attributes and payload.
+/// The payload is a string for the text formats, JSON for protobuf with a descriptor,
+/// and hexadecimal otherwise.
+#[must_use]
+pub fn message_to_json(msg: &UMessage, descriptor: Option<&MessageDescriptor>) -> Value {
+    let mut description = json!({
+        "source": msg.source.as_ref().map(format_uri),
+    });
+    if let Some(attributes) = &msg.attributes {
+        description["id"] = json!(attributes.id.as_ref().map(format_uuid));
+        // The uProtocol UUID carries its creation time
+        description["time"] = json!(attributes.id.as_ref().and_then(|id| {
+            Utc.timestamp_millis_opt(i64::try_from(id.msb >> 16).ok()?)
+                .single()
+                .map(|time| time.to_rfc3339())
+        }));
+        description["type"] = json!(enum_name(
+            UMessageType::try_from(attributes.r#type)
+                .ok()
+                .map(|t| t.as_str_name()),
+            "UMESSAGE_TYPE_"
+        ));
+        description["priority"] = json!(enum_name(
+            UPriority::try_from(attributes.priority)
+                .ok()
+                .map(|p| p.as_str_name()),
+            "UPRIORITY_"
+        ));
+        description["ttl"] = json!(attributes.ttl);
+        description["reqid"] = json!(attributes.reqid.as_ref().map(format_uuid));
+        description["commstatus"] = json!(attributes
+            .commstatus
+            .map(|code| enum_name(UCode::try_from(code).ok().map(|c| c.as_str_name()), "")));
+        description["sink"] = json!(attributes.sink.as_ref().map(format_uri));
+    }
+    if let Some(payload) = &msg.payload {
+        let format = UPayloadFormat::try_from(payload.format).ok();
+        description["format"] = json!(enum_name(
+            format.map(|f| f.as_str_name()),
+            "UPAYLOAD_FORMAT_"
+        ));
+        let data = match &payload.data {
+            Some(Data::Value(data)) => data.as_slice(),
+            _ => &[],
+        };
+        description["payload"] = match (format, descriptor) {
+            (Some(UPayloadFormat::UpayloadFormatProtobuf), Some(descriptor)) => {
+                DynamicMessage::decode(descriptor.clone(), data)
+                    .ok()
+                    .and_then(|message| serde_json::to_value(&message).ok())
+                    .unwrap_or_else(|| json!(hex(data)))
+            }
+            (Some(UPayloadFormat::UpayloadFormatJson), _) => {
+                serde_json::from_slice(data).unwrap_or_else(|_| json!(hex(data)))
+            }
+            (Some(UPayloadFormat::UpayloadFormatText), _) => {
+                json!(String::from_utf8_lossy(data))
+            }
+            _ => json!(hex(data)),
+        };
+    }
+    description
+}
+
+/// Describe the message on one line
+#[must_use]
+pub fn message_to_human(msg: &UMessage, descriptor: Option<&MessageDescriptor>) -> String {
+    let description = message_to_json(msg, descriptor);
+    let field = |name: &str| match &description[name] {
+        Value::Null => "-".to_string(),
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    };
+    // Only the failed responses have a status worth telling
+    let status = match &description["commstatus"] {
+        Value::String(code) if code != "ok" => format!(" commstatus={code}"),
+        _ => String::new(),
+    };
+    format!(
+        "[{}] {} {} id={} priority={} ttl={}{} format={}: {}",
+        field("time"),
+        field("source"),
+        field("type"),
+        field("id"),
+        field("priority"),
+        field("ttl"),
+        status,
+        field("format"),
+        field("payload"),
+    )
+}
+
+/// A Zenoh sample or query seen by `usniff`, decoded as far as it is a uProtocol one
+#[derive(Debug, Default)]
+pub struct SniffedMessage {
+    /// Whether it was a query rather than a sample
+    pub query: bool,
+    pub key: String,
+    /// `UUri` of the hexadecimal key
+    pub uri: Option<UUri>,
+    /// `UAttributes` of the attachment
+    pub attributes: Option<UAttributes>,
+    /// Payload format of the encoding suffix
+    pub format: Option<i32>,
+    pub size: usize,
+}
+
+impl SniffedMessage {
+    fn new(
+        query: bool,
+        key: &str,
+        attachment: Option<&Attachment>,
+        encoding: Option<&Encoding>,
+        size: usize,
+    ) -> Self {
+        SniffedMessage {
+            query,
+            key: key.to_string(),
+            uri: ULinkZenoh::from_zenoh_key_string(key).ok(),
+            attributes: codec::attachment_attributes(attachment).ok(),
+            format: encoding.and_then(|encoding| codec::decode_format(encoding).ok()),
+            size,
+        }
+    }
+
+    #[must_use]
+    pub fn from_sample(sample: &Sample) -> Self {
+        SniffedMessage::new(
+            false,
+            sample.key_expr.as_str(),
+            sample.attachment(),
+            Some(&sample.encoding),
+            sample.payload.len(),
+        )
+    }
+
+    #[must_use]
+    pub fn from_query(query: &Query) -> Self {
+        SniffedMessage::new(
+            true,
+            query.key_expr().as_str(),
+            query.attachment(),
+            query.value().map(|value| &value.encoding),
+            query.value().map_or(0, |value| value.payload.len()),
+        )
+    }
+
+    /// Header of the table printed by `usniff`
+    #[must_use]
+    pub fn header() -> String {
+        format!(
+            "{:<12} {:<8} {:<32} {:<32} {:<8} {:<36} {:>6} {:<8} {:>7}",
+            "TIME", "TYPE", "SOURCE", "SINK", "PRIORITY", "REQID", "TTL", "FORMAT", "SIZE"
+        )
+    }
+
+    /// Row of the table printed by `usniff`, the Zenoh key standing for the source if it
+    /// isn't a `UUri`
+    #[must_use]
+    pub fn row(&self) -> String {
+        let attributes = self.attributes.as_ref();
+        let message_type = match (attributes, self.query) {
+            (Some(attributes), _) => enum_name(
+                UMessageType::try_from(attributes.r#type)
+                    .ok()
+                    .map(|t| t.as_str_name()),
+                "UMESSAGE_TYPE_",
+            ),
+            (None, true) => "query".to_string(),
+            (None, false) => "-".to_string(),
+        };
+        let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+        format!(
+            "{:<12} {:<8} {:<32} {:<32} {:<8} {:<36} {:>6} {:<8} {:>7}",
+            Utc::now().format("%H:%M:%S%.3f"),
+            message_type,
+            self.uri
+                .as_ref()
+                .map_or_else(|| self.key.clone(), format_uri),
+            or_dash(attributes.and_then(|a| a.sink.as_ref()).map(format_uri)),
+            or_dash(attributes.map(|a| {
+                enum_name(
+                    UPriority::try_from(a.priority)
+                        .ok()
+                        .map(|p| p.as_str_name()),
+                    "UPRIORITY_",
+                )
+            })),
+            or_dash(attributes.and_then(|a| a.reqid.as_ref()).map(format_uuid)),
+            or_dash(attributes.and_then(|a| a.ttl).map(|ttl| ttl.to_string())),
+            or_dash(self.format.map(|format| {
+                enum_name(
+                    UPayloadFormat::try_from(format)
+                        .ok()
+                        .map(|f| f.as_str_name()),
+                    "UPAYLOAD_FORMAT_",
+                )
+            })),
+            self.size,
+        )
+    }
+}
+
+/// Hexadecimal form of the bytes
+#[must_use]
+pub fn hex(data: &[u8]) -> String {
+    data.iter()
+        .fold(String::new(), |s, b| s + &format!("{b:02x}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_uri() {
+        let uri = parse_uri("body.access@1234/1/0x162e").unwrap();
+        assert_eq!(uri.entity.as_ref().unwrap().name, "body.access");
+        assert_eq!(uri.entity.as_ref().unwrap().id, Some(1234));
+        assert_eq!(uri.resource.as_ref().unwrap().id, Some(5678));
+        assert_eq!(format_uri(&uri), "body.access@1234/1/5678");
+
+        let pattern = parse_uri("1234/*/*").unwrap();
+        assert!(pattern.resource.is_none());
+        assert!(matches(&pattern, &uri));
+        assert!(!matches(&parse_uri("1234/2/*").unwrap(), &uri));
+
+        assert!(parse_uri("1234/1").is_err());
+        assert!(parse_uri("1234/one/5678").is_err());
+    }
+
+    #[test]
+    fn test_parse_uri_forms() {
+        let micro = parse_uri("0100162e04d20100").unwrap();
+        assert_eq!(micro.entity.as_ref().unwrap().id, Some(1234));
+        assert_eq!(micro.resource.as_ref().unwrap().id, Some(5678));
+
+        let long = parse_uri("/body.access/1/door.front_left#Door").unwrap();
+        assert_eq!(long.entity.as_ref().unwrap().name, "body.access");
+        assert_eq!(long.entity.as_ref().unwrap().id, None);
+
+        assert!(parse_uri("0100162e04d2010").is_err());
+        assert_eq!(parse_priority("cs4"), Ok(UPriority::UpriorityCs4));
+        assert!(parse_priority("cs9").is_err());
+    }
+
+    #[test]
+    fn test_sniffed_row() {
+        let sniffed = SniffedMessage {
+            key: "0100162e04d20100".to_string(),
+            uri: Some(parse_uri("1234/1/5678").unwrap()),
+            attributes: Some(UAttributes {
+                r#type: UMessageType::UmessageTypePublish as i32,
+                priority: UPriority::UpriorityCs1 as i32,
+                ttl: Some(100),
+                ..Default::default()
+            }),
+            format: Some(UPayloadFormat::UpayloadFormatText as i32),
+            size: 5,
+            ..Default::default()
+        };
+        let row = sniffed.row();
+        for column in ["publish", "1234/1/5678", "cs1", "100", "text", "5"] {
+            assert!(row.contains(column), "{column} missing in {row}");
+        }
+
+        // Not a uProtocol key
+        let sniffed = SniffedMessage {
+            query: true,
+            key: "up/cache/0100162e04d20100".to_string(),
+            ..Default::default()
+        };
+        assert!(sniffed.row().contains("query    up/cache/0100162e04d20100"));
+    }
+}
@@ -0,0 +1,367 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Conversion between `UMessage`s and CloudEvents, following the uProtocol CloudEvent spec.
+//!
+//! The CloudEvent `id` is the message UUID and its `type` one of `pub.v1`, `req.v1` and
+//! `res.v1`. The `source` and the `sink` extension are `UUri`s in the `name@id/version/resource`
+//! form of [`crate::uri`] when they have ids, which drops the resource names, in the long form
+//! when they have the names only. They are read back from any of the forms of [`crate::uri`].
+//! The other `UAttributes` are the `priority`, `ttl`, `reqid`, `commstatus`,
+//! `permission_level` and `token` extensions, and the payload format is the `datacontenttype`.
+use crate::ULinkZenoh;
+use cloudevents::{
+    event::ExtensionValue, AttributesReader, Data, Event, EventBuilder, EventBuilderV10,
+};
+use uprotocol_sdk::{
+    uprotocol::{
+        Data as UData, UAttributes, UCode, UMessage, UMessageType, UPayload, UPayloadFormat,
+        UPriority, UStatus, UUri, Uuid,
+    },
+    uri::serializer::{LongUriSerializer, UriSerializer},
+};
+
+const TYPE_PUBLISH: &str = "pub.v1";
+const TYPE_REQUEST: &str = "req.v1";
+const TYPE_RESPONSE: &str = "res.v1";
+
+fn invalid(message: &str) -> UStatus {
+    UStatus::fail_with_code(UCode::InvalidArgument, message)
+}
+
+/// Format a uProtocol UUID the usual way
+#[must_use]
+pub fn uuid_to_string(uuid: &Uuid) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        uuid.msb >> 32,
+        (uuid.msb >> 16) & 0xffff,
+        uuid.msb & 0xffff,
+        uuid.lsb >> 48,
+        uuid.lsb & 0xffff_ffff_ffff
+    )
+}
+
+/// Parse a UUID formatted the usual way
+///
+/// # Errors
+/// Will return `Err` if it isn't a UUID
+pub fn uuid_from_string(uuid: &str) -> Result<Uuid, UStatus> {
+    let hex = uuid.replace('-', "");
+    if hex.len() != 32 || uuid.len() != 36 {
+        return Err(invalid("Invalid UUID"));
+    }
+    let value = u128::from_str_radix(&hex, 16).map_err(|_| invalid("Invalid UUID"))?;
+    Ok(Uuid {
+        msb: u64::try_from(value >> 64).map_err(|_| invalid("Invalid UUID"))?,
+        lsb: u64::try_from(value & u128::from(u64::MAX)).map_err(|_| invalid("Invalid UUID"))?,
+    })
+}
+
+// The form with the names and ids if it has ids, long one if it has the names only, micro one
+// otherwise
+fn uri_to_string(uri: &UUri) -> Result<String, UStatus> {
+    let entity = uri.entity.as_ref();
+    if entity.is_some_and(|e| e.id.is_some()) {
+        return Ok(crate::uri::format_uri(uri));
+    }
+    match LongUriSerializer::serialize(uri) {
+        Ok(long) if entity.is_some_and(|e| !e.name.is_empty()) => Ok(long),
+        _ => ULinkZenoh::to_zenoh_key_string(uri),
+    }
+}
+
+// Any of the forms, the one with the names and ids being the only one holding both
+fn uri_from_string(uri: &str) -> Result<UUri, UStatus> {
+    crate::uri::parse_uri(uri).map_err(|e| invalid(&e))
+}
+
+fn content_type(format: UPayloadFormat) -> Option<&'static str> {
+    match format {
+        UPayloadFormat::UpayloadFormatProtobuf => Some("application/x-protobuf"),
+        UPayloadFormat::UpayloadFormatJson => Some("application/json"),
+        UPayloadFormat::UpayloadFormatSomeip => Some("application/x-someip"),
+        UPayloadFormat::UpayloadFormatSomeipTlv => Some("application/x-someip_tlv"),
+        UPayloadFormat::UpayloadFormatRaw => Some("application/octet-stream"),
+        UPayloadFormat::UpayloadFormatText => Some("text/plain"),
+        UPayloadFormat::UpayloadFormatUnspecified => None,
+    }
+}
+
+fn payload_format(content_type: Option<&str>) -> UPayloadFormat {
+    // Ignore the parameters, e.g. text/plain; charset=utf-8
+    match content_type
+        .and_then(|c| c.split(';').next())
+        .map(str::trim)
+    {
+        Some("application/x-protobuf" | "application/protobuf") => {
+            UPayloadFormat::UpayloadFormatProtobuf
+        }
+        Some("application/json") => UPayloadFormat::UpayloadFormatJson,
+        Some("application/x-someip") => UPayloadFormat::UpayloadFormatSomeip,
+        Some("application/x-someip_tlv") => UPayloadFormat::UpayloadFormatSomeipTlv,
+        Some("application/octet-stream") => UPayloadFormat::UpayloadFormatRaw,
+        Some("text/plain") => UPayloadFormat::UpayloadFormatText,
+        _ => UPayloadFormat::UpayloadFormatUnspecified,
+    }
+}
+
+/// Convert the message into a CloudEvent
+///
+/// # Errors
+/// Will return `Err` if the message has no source or attributes, or has an invalid one
+pub fn umessage_to_cloudevent(msg: &UMessage) -> Result<Event, UStatus> {
+    let (Some(source), Some(attributes)) = (&msg.source, &msg.attributes) else {
+        return Err(invalid("The message needs a source and attributes"));
+    };
+    let id = attributes
+        .id
+        .as_ref()
+        .ok_or_else(|| invalid("The message has no id"))?;
+    let ty = match UMessageType::try_from(attributes.r#type) {
+        Ok(UMessageType::UmessageTypePublish) => TYPE_PUBLISH,
+        Ok(UMessageType::UmessageTypeRequest) => TYPE_REQUEST,
+        Ok(UMessageType::UmessageTypeResponse) => TYPE_RESPONSE,
+        _ => return Err(invalid("Unsupported message type")),
+    };
+
+    let mut builder = EventBuilderV10::new()
+        .id(uuid_to_string(id))
+        .ty(ty)
+        .source(uri_to_string(source)?);
+    if let Some(sink) = &attributes.sink {
+        builder = builder.extension("sink", uri_to_string(sink)?);
+    }
+    if let Ok(priority) = UPriority::try_from(attributes.priority) {
+        builder = builder.extension("priority", priority.as_str_name());
+    }
+    if let Some(ttl) = attributes.ttl {
+        builder = builder.extension("ttl", i64::from(ttl));
+    }
+    if let Some(reqid) = &attributes.reqid {
+        builder = builder.extension("reqid", uuid_to_string(reqid));
+    }
+    if let Some(commstatus) = attributes.commstatus {
+        builder = builder.extension("commstatus", i64::from(commstatus));
+    }
+    if let Some(level) = attributes.permission_level {
+        builder = builder.extension("permission_level", i64::from(level));
+    }
+    if let Some(token) = &attributes.token {
+        builder = builder.extension("token", token.as_str());
+    }
+    let mut data = None;
+    if let Some(payload) = &msg.payload {
+        let Some(UData::Value(value)) = &payload.data else {
+            return Err(invalid("Only payloads with a value are supported"));
+        };
+        let format = UPayloadFormat::try_from(payload.format)
+            .unwrap_or(UPayloadFormat::UpayloadFormatUnspecified);
+        let value = match format {
+            UPayloadFormat::UpayloadFormatText => String::from_utf8(value.clone())
+                .map_or_else(|e| Data::Binary(e.into_bytes()), Data::String),
+            _ => Data::Binary(value.clone()),
+        };
+        match content_type(format) {
+            Some(content_type) => builder = builder.data(content_type, value),
+            None => data = Some(value),
+        }
+    }
+    let mut event = builder
+        .build()
+        .map_err(|e| invalid(&format!("Unable to build the CloudEvent: {e}")))?;
+    // Without any datacontenttype
+    if let Some(data) = data {
+        event.set_data_unchecked(data);
+    }
+    Ok(event)
+}
+
+// Integer extension, also accepted as a string
+fn integer_extension(event: &Event, name: &str) -> Result<Option<i32>, UStatus> {
+    let value = match event.extension(name) {
+        None => return Ok(None),
+        Some(ExtensionValue::Integer(value)) => i32::try_from(*value).ok(),
+        Some(ExtensionValue::String(value)) => value.parse().ok(),
+        Some(ExtensionValue::Boolean(_)) => None,
+    };
+    value
+        .map(Some)
+        .ok_or_else(|| invalid(&format!("Invalid {name} extension")))
+}
+
+fn string_extension(event: &Event, name: &str) -> Option<String> {
+    match event.extension(name)? {
+        ExtensionValue::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+/// Convert the CloudEvent back into a message
+///
+/// # Errors
+/// Will return `Err` if the CloudEvent doesn't follow the uProtocol CloudEvent spec
+pub fn cloudevent_to_umessage(event: &Event) -> Result<UMessage, UStatus> {
+    let r#type = match event.ty() {
+        TYPE_PUBLISH => UMessageType::UmessageTypePublish,
+        TYPE_REQUEST => UMessageType::UmessageTypeRequest,
+        TYPE_RESPONSE => UMessageType::UmessageTypeResponse,
+        _ => return Err(invalid("Not a uProtocol CloudEvent type")),
+    };
+    let priority = match string_extension(event, "priority") {
+        Some(priority) => UPriority::from_str_name(&priority)
+            .ok_or_else(|| invalid("Invalid priority extension"))?,
+        None => UPriority::UpriorityUnspecified,
+    };
+    let attributes = UAttributes {
+        id: Some(uuid_from_string(event.id())?),
+        r#type: r#type as i32,
+        sink: string_extension(event, "sink")
+            .map(|sink| uri_from_string(&sink))
+            .transpose()?,
+        priority: priority as i32,
+        ttl: integer_extension(event, "ttl")?,
+        permission_level: integer_extension(event, "permission_level")?,
+        commstatus: integer_extension(event, "commstatus")?,
+        reqid: string_extension(event, "reqid")
+            .map(|reqid| uuid_from_string(&reqid))
+            .transpose()?,
+        token: string_extension(event, "token"),
+    };
+    let payload = event.data().map(|data| {
+        let data = match data {
+            Data::Binary(data) => data.clone(),
+            Data::String(data) => data.as_bytes().to_vec(),
+            Data::Json(data) => data.to_string().into_bytes(),
+        };
+        UPayload {
+            length: Some(0),
+            format: payload_format(event.datacontenttype()) as i32,
+            data: Some(UData::Value(data)),
+        }
+    });
+    Ok(UMessage {
+        source: Some(uri_from_string(&event.source().to_string())?),
+        attributes: Some(attributes),
+        payload,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::{
+        transport::builder::UAttributesBuilder,
+        uprotocol::{UEntity, UResource},
+        uuid::builder::UUIDv8Builder,
+    };
+
+    fn message(source: UUri, attributes: UAttributes, format: UPayloadFormat) -> UMessage {
+        UMessage {
+            source: Some(source),
+            attributes: Some(attributes),
+            payload: Some(UPayload {
+                length: Some(0),
+                format: format as i32,
+                data: Some(UData::Value(b"{\"open\":true}".to_vec())),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_uuid_string() {
+        let uuid = UUIDv8Builder::new().build();
+        assert_eq!(uuid_from_string(&uuid_to_string(&uuid)).unwrap(), uuid);
+        assert!(uuid_from_string("not-a-uuid").is_err());
+    }
+
+    #[test]
+    fn test_publish_round_trip() {
+        let topic = UUri {
+            entity: Some(UEntity {
+                name: "body.access".to_string(),
+                version_major: Some(1),
+                ..Default::default()
+            }),
+            resource: Some(UResource {
+                name: "door".to_string(),
+                instance: Some("front_left".to_string()),
+                message: Some("Door".to_string()),
+                id: None,
+            }),
+            ..Default::default()
+        };
+        let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs1)
+            .with_ttl(100)
+            .build();
+        let msg = message(topic, attributes, UPayloadFormat::UpayloadFormatJson);
+
+        let event = umessage_to_cloudevent(&msg).unwrap();
+        assert_eq!(event.ty(), "pub.v1");
+        assert_eq!(event.datacontenttype(), Some("application/json"));
+        assert_eq!(
+            event.extension("priority"),
+            Some(&ExtensionValue::from("UPRIORITY_CS1"))
+        );
+        assert_eq!(cloudevent_to_umessage(&event).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_uri_with_ids() {
+        let topic = UUri {
+            entity: Some(UEntity {
+                name: "body.access".to_string(),
+                version_major: Some(1),
+                id: Some(1234),
+                ..Default::default()
+            }),
+            resource: Some(UResource {
+                name: "door".to_string(),
+                instance: Some("front_left".to_string()),
+                message: Some("Door".to_string()),
+                id: Some(5678),
+            }),
+            ..Default::default()
+        };
+        let msg = message(
+            topic,
+            UAttributesBuilder::publish(UPriority::UpriorityCs1).build(),
+            UPayloadFormat::UpayloadFormatJson,
+        );
+
+        // The ids are kept, so the message can be sent back
+        let event = umessage_to_cloudevent(&msg).unwrap();
+        assert_eq!(event.source().to_string(), "body.access@1234/1/5678");
+        let source = cloudevent_to_umessage(&event).unwrap().source.unwrap();
+        assert_eq!(source.entity.unwrap().id, Some(1234));
+        assert_eq!(source.resource.unwrap().id, Some(5678));
+    }
+
+    #[test]
+    fn test_response_round_trip() {
+        // Received with a pattern, so without names
+        let method = ULinkZenoh::from_zenoh_key_string("0100162e04d20100").unwrap();
+        let mut attributes =
+            UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 1000)
+                .with_reqid(UUIDv8Builder::new().build())
+                .build();
+        attributes.r#type = UMessageType::UmessageTypeResponse as i32;
+        attributes.commstatus = Some(UCode::Unavailable as i32);
+        let msg = message(method, attributes, UPayloadFormat::UpayloadFormatProtobuf);
+
+        let event = umessage_to_cloudevent(&msg).unwrap();
+        assert_eq!(event.ty(), "res.v1");
+        assert_eq!(event.source().to_string(), "0100162e04d20100");
+        assert_eq!(cloudevent_to_umessage(&event).unwrap(), msg);
+    }
+}
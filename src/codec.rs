@@ -0,0 +1,251 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! How the uProtocol messages are carried by Zenoh.
+//!
+//! The `UAttributes` are serialized with protobuf into the `uattributes` item of the attachment.
+//! The `UPayloadFormat` is mapped to the closest standard Zenoh encoding, so that the other Zenoh
+//! tools can make sense of the payload:
+//!
+//! | `UPayloadFormat` | Zenoh encoding                       |
+//! |------------------|--------------------------------------|
+//! | `Protobuf`       | `application/octet-stream;protobuf`  |
+//! | `Json`           | `application/json`                   |
+//! | `Text`           | `text/plain`                         |
+//! | `Raw`            | `application/octet-stream`           |
+//! | others           | `application/custom` + format number |
+//!
+//! The format number suffix of `application/custom` is the scheme of the former versions, so it's
+//! still accepted for any format. The decoding functions take whatever any peer sent, so they
+//! return an error status instead of panicking.
+use prost::Message;
+use uprotocol_sdk::uprotocol::{Data, UAttributes, UCode, UPayload, UPayloadFormat, UStatus};
+use zenoh::{
+    prelude::{Encoding, KnownEncoding},
+    sample::{Attachment, AttachmentBuilder},
+};
+
+/// Attachment item holding the serialized `UAttributes`
+pub const UATTRIBUTES_KEY: &str = "uattributes";
+
+/// Suffix of the `application/octet-stream` encoding telling the payload is protobuf
+pub const PROTOBUF_SUFFIX: &str = ";protobuf";
+
+/// Serialize the `UAttributes` with protobuf
+#[must_use]
+pub fn encode_attributes(attributes: &UAttributes) -> Vec<u8> {
+    attributes.encode_to_vec()
+}
+
+/// Deserialize the `UAttributes`
+///
+/// # Errors
+/// Will return `Err` if the bytes aren't a protobuf `UAttributes`
+pub fn decode_attributes(buf: &[u8]) -> Result<UAttributes, UStatus> {
+    UAttributes::decode(buf)
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to decode attribute"))
+}
+
+/// Attachment holding the serialized `UAttributes`, other items may be added to it
+#[must_use]
+pub fn attachment(attributes: &[u8]) -> AttachmentBuilder {
+    let mut attachment = AttachmentBuilder::new();
+    attachment.insert(UATTRIBUTES_KEY, attributes);
+    attachment
+}
+
+/// Get the serialized `UAttributes` from the attachment, as sent
+///
+/// # Errors
+/// Will return `Err` if there is no attachment, or no `UAttributes` in it
+pub fn attachment_raw_attributes(attachment: Option<&Attachment>) -> Result<Vec<u8>, UStatus> {
+    let Some(attachment) = attachment else {
+        return Err(UStatus::fail_with_code(
+            UCode::Internal,
+            "Unable to get attachment",
+        ));
+    };
+    let Some(attributes) = attachment.get(&UATTRIBUTES_KEY.as_bytes()) else {
+        return Err(UStatus::fail_with_code(
+            UCode::Internal,
+            "Unable to get uattributes",
+        ));
+    };
+    Ok(attributes.to_vec())
+}
+
+/// Get back the `UAttributes` from the attachment
+///
+/// # Errors
+/// Will return `Err` if there is no attachment, or no valid `UAttributes` in it
+pub fn attachment_attributes(attachment: Option<&Attachment>) -> Result<UAttributes, UStatus> {
+    decode_attributes(&attachment_raw_attributes(attachment)?)
+}
+
+/// Zenoh encoding of the payload format
+#[must_use]
+pub fn encoding(format: i32) -> Encoding {
+    match UPayloadFormat::try_from(format) {
+        Ok(UPayloadFormat::UpayloadFormatProtobuf) => {
+            Encoding::WithSuffix(KnownEncoding::AppOctetStream, PROTOBUF_SUFFIX.into())
+        }
+        Ok(UPayloadFormat::UpayloadFormatJson) => Encoding::Exact(KnownEncoding::AppJson),
+        Ok(UPayloadFormat::UpayloadFormatText) => Encoding::Exact(KnownEncoding::TextPlain),
+        Ok(UPayloadFormat::UpayloadFormatRaw) => Encoding::Exact(KnownEncoding::AppOctetStream),
+        _ => Encoding::WithSuffix(KnownEncoding::AppCustom, format.to_string().into()),
+    }
+}
+
+/// Get back the payload format from the suffix of the legacy `application/custom` encoding
+///
+/// # Errors
+/// Will return `Err` if the suffix isn't a number
+pub fn decode_format_suffix(suffix: &str) -> Result<i32, UStatus> {
+    suffix
+        .parse::<i32>()
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to get payload encoding"))
+}
+
+// Whether the suffix of the encoding is only MIME parameters, e.g. `;charset=utf-8`
+fn is_mime_parameters(suffix: &str) -> bool {
+    suffix.is_empty()
+        || (suffix.starts_with(';')
+            && suffix
+                .split(';')
+                .skip(1)
+                .all(|parameter| parameter.contains('=')))
+}
+
+/// Get back the payload format from the Zenoh encoding, in the standard or the legacy scheme.
+/// The MIME parameters of the standard encodings, e.g. `text/plain;charset=utf-8`, are ignored.
+///
+/// # Errors
+/// Will return `Err` if the encoding doesn't hold a payload format
+pub fn decode_format(encoding: &Encoding) -> Result<i32, UStatus> {
+    let format = match (encoding.prefix(), encoding.suffix()) {
+        (KnownEncoding::AppCustom, suffix) => return decode_format_suffix(suffix),
+        (KnownEncoding::AppOctetStream, PROTOBUF_SUFFIX) => UPayloadFormat::UpayloadFormatProtobuf,
+        (KnownEncoding::AppJson | KnownEncoding::TextJson, suffix)
+            if is_mime_parameters(suffix) =>
+        {
+            UPayloadFormat::UpayloadFormatJson
+        }
+        (KnownEncoding::TextPlain, suffix) if is_mime_parameters(suffix) => {
+            UPayloadFormat::UpayloadFormatText
+        }
+        (KnownEncoding::AppOctetStream, suffix) if is_mime_parameters(suffix) => {
+            UPayloadFormat::UpayloadFormatRaw
+        }
+        _ => {
+            return Err(UStatus::fail_with_code(
+                UCode::Internal,
+                "Unable to get payload encoding",
+            ))
+        }
+    };
+    Ok(format as i32)
+}
+
+/// Rebuild the `UAttributes` and the `UPayload` from the raw parts of a Zenoh sample or query
+///
+/// # Errors
+/// Will return `Err` if the attributes or the payload format can't be decoded
+pub fn decode_message(
+    attributes: &[u8],
+    encoding: &Encoding,
+    payload: &[u8],
+) -> Result<(UAttributes, UPayload), UStatus> {
+    let attributes = decode_attributes(attributes)?;
+    let payload = UPayload {
+        length: Some(0),
+        format: decode_format(encoding)?,
+        data: Some(Data::Value(payload.to_vec())),
+    };
+    Ok((attributes, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::uprotocol::{UPayloadFormat, UPriority};
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(decode_attributes(&[0xff, 0xff, 0xff]).is_err());
+        assert!(decode_format_suffix("").is_err());
+        assert!(decode_format_suffix("protobuf").is_err());
+        assert!(decode_format(&Encoding::Exact(KnownEncoding::ImagePng)).is_err());
+        assert!(decode_format(&Encoding::WithSuffix(
+            KnownEncoding::TextPlain,
+            ";protobuf".into()
+        ))
+        .is_err());
+        assert!(attachment_attributes(None).is_err());
+        let other = AttachmentBuilder::new().build();
+        assert!(attachment_attributes(Some(&other)).is_err());
+    }
+
+    #[test]
+    fn test_decode_message() {
+        let attributes = UAttributes {
+            priority: UPriority::UpriorityCs4 as i32,
+            ttl: Some(1000),
+            ..Default::default()
+        };
+        let format = UPayloadFormat::UpayloadFormatText as i32;
+        let (decoded, payload) =
+            decode_message(&encode_attributes(&attributes), &encoding(format), b"Hello").unwrap();
+        assert_eq!(decoded, attributes);
+        assert_eq!(payload.format, format);
+        assert_eq!(payload.data, Some(Data::Value(b"Hello".to_vec())));
+        let attachment = attachment(&encode_attributes(&attributes)).build();
+        assert_eq!(attachment_attributes(Some(&attachment)), Ok(attributes));
+    }
+
+    #[test]
+    fn test_encoding() {
+        let json = UPayloadFormat::UpayloadFormatJson as i32;
+        assert_eq!(encoding(json), Encoding::Exact(KnownEncoding::AppJson));
+        assert_eq!(
+            encoding(UPayloadFormat::UpayloadFormatProtobuf as i32).to_string(),
+            "application/octet-stream;protobuf"
+        );
+        let someip = UPayloadFormat::UpayloadFormatSomeip as i32;
+        assert_eq!(decode_format(&encoding(someip)), Ok(someip));
+
+        // With MIME parameters
+        let text = Encoding::WithSuffix(KnownEncoding::TextPlain, ";charset=latin1".into());
+        assert_eq!(
+            decode_format(&text),
+            Ok(UPayloadFormat::UpayloadFormatText as i32)
+        );
+        let json = Encoding::WithSuffix(KnownEncoding::AppJson, ";charset=utf-8".into());
+        assert_eq!(
+            decode_format(&json),
+            Ok(UPayloadFormat::UpayloadFormatJson as i32)
+        );
+
+        // Legacy scheme
+        for format in [
+            UPayloadFormat::UpayloadFormatProtobuf,
+            UPayloadFormat::UpayloadFormatJson,
+            UPayloadFormat::UpayloadFormatText,
+            UPayloadFormat::UpayloadFormatRaw,
+        ] {
+            let legacy =
+                Encoding::WithSuffix(KnownEncoding::AppCustom, (format as i32).to_string().into());
+            assert_eq!(decode_format(&legacy), Ok(format as i32));
+            assert_eq!(decode_format(&encoding(format as i32)), Ok(format as i32));
+        }
+    }
+}
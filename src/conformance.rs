@@ -0,0 +1,609 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Conformance checks of the uProtocol transport semantics, to run against any uLink.
+//!
+//! Each check panics with the expectation the uLink fails. The uLink must deliver the messages
+//! and requests it sends to its own listeners, like a `ULinkZenoh` created with
+//! [`crate::ULinkZenoh::from_session`] on a [`crate::testing::loopback_session`]. The checks use
+//! their own topics and methods, so they can run one after the other on the same uLink, and
+//! [`run_all`] runs them all.
+//!
+//! The checks only need the uProtocol APIs, see [`Transport`], except [`rpc_error`] which checks
+//! the `commstatus` of the response: it needs the uLink to implement [`ULink`] to return the whole
+//! responses of the RPC calls. [`run_transport`] runs all the other checks.
+use crate::{rt, ULinkZenoh};
+use async_trait::async_trait;
+use prost::Message;
+use std::sync::Arc;
+use std::time::Duration;
+use uprotocol_sdk::{
+    rpc::{RpcClient, RpcServer},
+    transport::{builder::UAttributesBuilder, datamodel::UTransport},
+    uprotocol::{
+        Data, UAttributes, UCode, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat,
+        UPriority, UResource, UStatus, UUri,
+    },
+    uri::builder::resourcebuilder::UResourceBuilder,
+    uuid::builder::UUIDv8Builder,
+};
+
+// Upper bound of the waits for the messages which are expected
+const TIMEOUT: Duration = Duration::from_secs(5);
+// How long to wait for the messages which must not come
+const SILENCE: Duration = Duration::from_millis(200);
+// Delay of a response, longer than the default RPC timeout
+const SLOW_RESPONSE: Duration = Duration::from_millis(1500);
+
+/// A uLink with the uProtocol transport and RPC APIs
+pub trait Transport: UTransport + RpcClient + RpcServer + Send + Sync + 'static {}
+
+impl<T: UTransport + RpcClient + RpcServer + Send + Sync + 'static> Transport for T {}
+
+/// A uLink returning the whole responses of the RPC calls
+#[async_trait]
+pub trait ULink: Transport {
+    /// Invoke the method like `invoke_method`, but get the whole response `UMessage`, so that its
+    /// `commstatus` is checked
+    async fn invoke_method_message(
+        &self,
+        topic: UUri,
+        payload: UPayload,
+        attributes: UAttributes,
+    ) -> Result<UMessage, UStatus>;
+}
+
+#[async_trait]
+impl ULink for ULinkZenoh {
+    async fn invoke_method_message(
+        &self,
+        topic: UUri,
+        payload: UPayload,
+        attributes: UAttributes,
+    ) -> Result<UMessage, UStatus> {
+        ULinkZenoh::invoke_method_message(self, topic, payload, attributes).await
+    }
+}
+
+/// Run all the conformance checks
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn run_all<T: ULink>(ulink: &Arc<T>) {
+    run_transport(ulink).await;
+    rpc_error(ulink).await;
+}
+
+/// Run all the conformance checks needing only the uProtocol APIs, i.e. all but [`rpc_error`]
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn run_transport<T: Transport>(ulink: &Arc<T>) {
+    publish_subscribe(ulink.as_ref()).await;
+    notification(ulink.as_ref()).await;
+    unregister(ulink.as_ref()).await;
+    attributes_round_trip(ulink.as_ref()).await;
+    payload_formats(ulink.as_ref()).await;
+    priorities(ulink.as_ref()).await;
+    rpc_success(ulink).await;
+    rpc_timeout(ulink).await;
+}
+
+/// A published message reaches the listener of its topic, with its payload and its topic as source
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn publish_subscribe<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0001);
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    ulink
+        .send(
+            topic.clone(),
+            text_payload("Hello World!"),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .await
+        .expect("Unable to publish");
+
+    let msg = recv(&receiver).await.expect("The message isn't received");
+    assert_eq!(msg.source, Some(topic.clone()), "Wrong source");
+    let attributes = msg.attributes.expect("The message has no attributes");
+    assert_eq!(
+        attributes.r#type,
+        UMessageType::UmessageTypePublish as i32,
+        "Wrong message type"
+    );
+    assert_eq!(
+        payload_data(msg.payload.as_ref()),
+        Some(b"Hello World!".as_slice()),
+        "Wrong payload"
+    );
+    ulink
+        .unregister_listener(topic, &listener)
+        .await
+        .expect("Unable to unregister the listener");
+}
+
+/// A notification, i.e. a published message with a sink, reaches the listener of its topic with
+/// the sink kept
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn notification<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0002);
+    let sink = UUri {
+        entity: Some(UEntity {
+            name: "conformance.sink".to_string(),
+            version_major: Some(1),
+            id: Some(0x7ffe),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    let attributes = UAttributes {
+        sink: Some(sink.clone()),
+        ..UAttributesBuilder::publish(UPriority::UpriorityCs4).build()
+    };
+    ulink
+        .send(topic.clone(), text_payload("Notification"), attributes)
+        .await
+        .expect("Unable to notify");
+
+    let msg = recv(&receiver)
+        .await
+        .expect("The notification isn't received");
+    let attributes = msg.attributes.expect("The message has no attributes");
+    assert_eq!(attributes.sink, Some(sink), "Wrong sink");
+    ulink
+        .unregister_listener(topic, &listener)
+        .await
+        .expect("Unable to unregister the listener");
+}
+
+/// An unregistered listener receives nothing more and can't be unregistered again, for the topics
+/// and the RPC methods
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn unregister<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0003);
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    ulink
+        .unregister_listener(topic.clone(), &listener)
+        .await
+        .expect("Unable to unregister the listener");
+    assert!(
+        ulink
+            .unregister_listener(topic.clone(), &listener)
+            .await
+            .is_err(),
+        "The listener is unregistered twice"
+    );
+    ulink
+        .send(
+            topic,
+            text_payload("Nobody listens"),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .await
+        .expect("Unable to publish");
+    assert!(
+        rt::timeout(SILENCE, receiver.recv_async()).await.is_none(),
+        "An unregistered listener receives messages"
+    );
+
+    let method = method("Unregister", 0x0003);
+    let listener = ulink
+        .register_rpc_listener(method.clone(), Box::new(|_| {}))
+        .await
+        .expect("Unable to register the RPC listener");
+    ulink
+        .unregister_rpc_listener(method.clone(), &listener)
+        .await
+        .expect("Unable to unregister the RPC listener");
+    assert!(
+        ulink
+            .unregister_rpc_listener(method, &listener)
+            .await
+            .is_err(),
+        "The RPC listener is unregistered twice"
+    );
+}
+
+/// The listener receives the attributes as they were sent
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn attributes_round_trip<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0004);
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    let attributes = UAttributes {
+        ttl: Some(60_000),
+        permission_level: Some(2),
+        token: Some("conformance".to_string()),
+        ..UAttributesBuilder::publish(UPriority::UpriorityCs2).build()
+    };
+    ulink
+        .send(
+            topic.clone(),
+            text_payload("Attributes"),
+            attributes.clone(),
+        )
+        .await
+        .expect("Unable to publish");
+
+    let msg = recv(&receiver).await.expect("The message isn't received");
+    assert_eq!(msg.attributes, Some(attributes), "Wrong attributes");
+    ulink
+        .unregister_listener(topic, &listener)
+        .await
+        .expect("Unable to unregister the listener");
+}
+
+/// The listener receives the payload format as it was sent
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn payload_formats<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0005);
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    for format in [
+        UPayloadFormat::UpayloadFormatUnspecified,
+        UPayloadFormat::UpayloadFormatProtobuf,
+        UPayloadFormat::UpayloadFormatJson,
+        UPayloadFormat::UpayloadFormatSomeip,
+        UPayloadFormat::UpayloadFormatSomeipTlv,
+        UPayloadFormat::UpayloadFormatRaw,
+        UPayloadFormat::UpayloadFormatText,
+    ] {
+        let payload = UPayload {
+            format: format as i32,
+            ..text_payload("{}")
+        };
+        ulink
+            .send(
+                topic.clone(),
+                payload,
+                UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+            )
+            .await
+            .expect("Unable to publish");
+
+        let msg = recv(&receiver).await.expect("The message isn't received");
+        let payload = msg.payload.expect("The message has no payload");
+        assert_eq!(
+            payload.format,
+            format as i32,
+            "Wrong payload format for {}",
+            format.as_str_name()
+        );
+        assert_eq!(payload_data(Some(&payload)), Some(b"{}".as_slice()));
+    }
+    ulink
+        .unregister_listener(topic, &listener)
+        .await
+        .expect("Unable to unregister the listener");
+}
+
+/// The messages of every priority are delivered, with their priority
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn priorities<T: UTransport>(ulink: &T) {
+    let topic = topic(0x0006);
+    let (listener, receiver) = subscribe(ulink, &topic).await;
+    for priority in [
+        UPriority::UpriorityCs0,
+        UPriority::UpriorityCs1,
+        UPriority::UpriorityCs2,
+        UPriority::UpriorityCs3,
+        UPriority::UpriorityCs4,
+        UPriority::UpriorityCs5,
+        UPriority::UpriorityCs6,
+    ] {
+        ulink
+            .send(
+                topic.clone(),
+                text_payload(priority.as_str_name()),
+                UAttributesBuilder::publish(priority).build(),
+            )
+            .await
+            .expect("Unable to publish");
+
+        let msg = recv(&receiver)
+            .await
+            .unwrap_or_else(|| panic!("The {} message isn't received", priority.as_str_name()));
+        let attributes = msg.attributes.expect("The message has no attributes");
+        assert_eq!(
+            attributes.priority,
+            priority as i32,
+            "Wrong priority for {}",
+            priority.as_str_name()
+        );
+    }
+    ulink
+        .unregister_listener(topic, &listener)
+        .await
+        .expect("Unable to unregister the listener");
+}
+
+/// The RPC listener receives the request with its attributes, and its response is returned to the
+/// caller
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn rpc_success<T: Transport>(ulink: &Arc<T>) {
+    let method = method("Echo", 0x0007);
+    let (sender, requests) = flume::unbounded();
+    let listener = serve(ulink, &method, move |request| {
+        let payload = request.payload.clone();
+        let _ = sender.send(request);
+        payload.map(|payload| (payload, None))
+    })
+    .await;
+    let attributes = request_attributes(&method);
+
+    let response = ulink
+        .invoke_method(method.clone(), text_payload("Echo"), attributes.clone())
+        .await
+        .expect("The RPC call fails");
+    assert_eq!(
+        payload_data(Some(&response)),
+        Some(b"Echo".as_slice()),
+        "Wrong response"
+    );
+
+    let request = requests.try_recv().expect("The RPC listener isn't called");
+    assert_eq!(request.source, Some(method.clone()), "Wrong request source");
+    assert_eq!(
+        request.attributes,
+        Some(attributes),
+        "Wrong request attributes"
+    );
+    ulink
+        .unregister_rpc_listener(method, &listener)
+        .await
+        .expect("Unable to unregister the RPC listener");
+}
+
+/// The response of a failed RPC call, holding its `UStatus`, is returned to the caller
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn rpc_error<T: ULink>(ulink: &Arc<T>) {
+    let method = method("Fail", 0x0008);
+    let status = UStatus::fail_with_code(UCode::InvalidArgument, "Conformance failure");
+    let status_cloned = status.clone();
+    let listener = serve(ulink, &method, move |_| {
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatProtobuf as i32,
+            data: Some(Data::Value(status_cloned.encode_to_vec())),
+        };
+        Some((payload, Some(status_cloned.code)))
+    })
+    .await;
+
+    let response = ulink
+        .invoke_method_message(
+            method.clone(),
+            text_payload("Fail"),
+            request_attributes(&method),
+        )
+        .await
+        .expect("The RPC call fails");
+    assert_eq!(
+        response
+            .attributes
+            .and_then(|attributes| attributes.commstatus),
+        Some(status.code),
+        "Wrong commstatus"
+    );
+    let received = payload_data(response.payload.as_ref())
+        .and_then(|data| UStatus::decode(data).ok())
+        .expect("The response doesn't hold a UStatus");
+    assert_eq!(received, status, "Wrong status");
+    ulink
+        .unregister_rpc_listener(method, &listener)
+        .await
+        .expect("Unable to unregister the RPC listener");
+}
+
+/// An RPC call fails in bounded time when the method isn't served, or isn't answered, but waits
+/// for a response slower than 1s during the TTL of the request
+///
+/// # Panics
+/// Will panic if the uLink doesn't conform
+pub async fn rpc_timeout<T: Transport>(ulink: &Arc<T>) {
+    let method = method("Silent", 0x0009);
+    let result = rt::timeout(
+        TIMEOUT,
+        ulink.invoke_method(
+            method.clone(),
+            text_payload("Nobody serves"),
+            request_attributes(&method),
+        ),
+    )
+    .await
+    .expect("The RPC call of a method not served doesn't return");
+    assert!(
+        result.is_err(),
+        "The RPC call of a method not served succeeds"
+    );
+
+    let listener = serve(ulink, &method, |_| None).await;
+    let result = rt::timeout(
+        TIMEOUT,
+        ulink.invoke_method(
+            method.clone(),
+            text_payload("Nobody answers"),
+            request_attributes(&method),
+        ),
+    )
+    .await
+    .expect("The RPC call of a method not answered doesn't return");
+    assert!(
+        result.is_err(),
+        "The RPC call of a method not answered succeeds"
+    );
+    ulink
+        .unregister_rpc_listener(method, &listener)
+        .await
+        .expect("Unable to unregister the RPC listener");
+
+    let method = method("Slow", 0x000a);
+    let listener = serve_delayed(ulink, &method, SLOW_RESPONSE, |request| {
+        request.payload.map(|payload| (payload, None))
+    })
+    .await;
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 3000)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let response = ulink
+        .invoke_method(method.clone(), text_payload("Take your time"), attributes)
+        .await
+        .expect("The RPC call fails before the TTL of the request");
+    assert_eq!(
+        payload_data(Some(&response)),
+        Some(b"Take your time".as_slice()),
+        "Wrong response"
+    );
+    ulink
+        .unregister_rpc_listener(method, &listener)
+        .await
+        .expect("Unable to unregister the RPC listener");
+}
+
+fn entity() -> UEntity {
+    UEntity {
+        name: "conformance.test".to_string(),
+        version_major: Some(1),
+        id: Some(0x7fff),
+        ..Default::default()
+    }
+}
+
+fn topic(id: u32) -> UUri {
+    UUri {
+        entity: Some(entity()),
+        resource: Some(UResource {
+            name: "test".to_string(),
+            instance: Some(format!("{id:04x}")),
+            message: Some("Conformance".to_string()),
+            id: Some(id),
+        }),
+        ..Default::default()
+    }
+}
+
+fn method(name: &str, id: u32) -> UUri {
+    UUri {
+        entity: Some(entity()),
+        resource: Some(UResourceBuilder::for_rpc_request(
+            Some(name.to_string()),
+            Some(id),
+        )),
+        ..Default::default()
+    }
+}
+
+fn text_payload(text: &str) -> UPayload {
+    UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(text.as_bytes().to_vec())),
+    }
+}
+
+fn payload_data(payload: Option<&UPayload>) -> Option<&[u8]> {
+    // Only the payloads held by value are supported
+    let Data::Value(value) = payload?.data.as_ref()? else {
+        return None;
+    };
+    Some(value)
+}
+
+fn request_attributes(method: &UUri) -> UAttributes {
+    UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 1000)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build()
+}
+
+// Register a listener of the topic, forwarding its messages to the returned receiver
+async fn subscribe<T: UTransport>(ulink: &T, topic: &UUri) -> (String, flume::Receiver<UMessage>) {
+    let (sender, receiver) = flume::unbounded();
+    let listener = ulink
+        .register_listener(
+            topic.clone(),
+            Box::new(move |result| {
+                if let Ok(msg) = result {
+                    let _ = sender.send(msg);
+                }
+            }),
+        )
+        .await
+        .expect("Unable to register the listener");
+    (listener, receiver)
+}
+
+async fn recv(receiver: &flume::Receiver<UMessage>) -> Option<UMessage> {
+    rt::timeout(TIMEOUT, receiver.recv_async()).await?.ok()
+}
+
+// Serve the method with the handler returning the response payload and commstatus, or `None` to
+// never answer
+async fn serve<T, F>(ulink: &Arc<T>, method: &UUri, handler: F) -> String
+where
+    T: Transport,
+    F: Fn(UMessage) -> Option<(UPayload, Option<i32>)> + Send + Sync + 'static,
+{
+    serve_delayed(ulink, method, Duration::ZERO, handler).await
+}
+
+// Same as `serve`, sending the response after the delay
+async fn serve_delayed<T, F>(ulink: &Arc<T>, method: &UUri, delay: Duration, handler: F) -> String
+where
+    T: Transport,
+    F: Fn(UMessage) -> Option<(UPayload, Option<i32>)> + Send + Sync + 'static,
+{
+    // The uLink holds its listeners, so they only keep a weak reference to it
+    let weak = Arc::downgrade(ulink);
+    ulink
+        .register_rpc_listener(
+            method.clone(),
+            Box::new(move |result: Result<UMessage, UStatus>| {
+                let (Ok(request), Some(ulink)) = (result, weak.upgrade()) else {
+                    return;
+                };
+                let (Some(source), Some(mut attributes)) =
+                    (request.source.clone(), request.attributes.clone())
+                else {
+                    return;
+                };
+                let Some((payload, commstatus)) = handler(request) else {
+                    return;
+                };
+                attributes.set_type(UMessageType::UmessageTypeResponse);
+                attributes.commstatus = commstatus;
+                rt::spawn(async move {
+                    if !delay.is_zero() {
+                        rt::sleep(delay).await;
+                    }
+                    let _ = ulink.send(source, payload, attributes).await;
+                });
+            }),
+        )
+        .await
+        .expect("Unable to register the RPC listener")
+}
@@ -0,0 +1,296 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! End-to-end encryption of the messages, so that the Zenoh routers only see ciphertext.
+//!
+//! For the topics and RPC methods the [`KeyProvider`] of the uLink has a key for, the payload and
+//! the `token` and `permission_level` attributes are sealed with ChaCha20-Poly1305 into the
+//! payload, as the random nonce followed by the ciphertext. The other attributes and the payload
+//! format stay in clear, as they are needed to route and expire the messages, but are
+//! authenticated with the Zenoh key of the topic, so that a message can't be replayed on another
+//! topic sharing the key. A message which can't be opened with the key, e.g. tampered with or sent in
+//! clear, is delivered as a `PermissionDenied` error.
+use crate::{codec, ULinkZenoh};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use prost::Message;
+use std::collections::HashMap;
+use uprotocol_sdk::uprotocol::{Data, UAttributes, UCode, UMessage, UPayload, UStatus, UUri};
+
+// Length of the ChaCha20-Poly1305 nonce
+const NONCE_LEN: usize = 12;
+
+/// Resolve the key of the topics and RPC methods to encrypt
+pub trait KeyProvider: Send + Sync {
+    /// The 256-bit key of the topic or RPC method, `None` to send and receive it in clear
+    fn key(&self, uri: &UUri) -> Option<[u8; 32]>;
+}
+
+/// Fixed keys of the topics and RPC methods
+#[derive(Default)]
+pub struct StaticKeys {
+    // Keys by Zenoh key, as the received messages only have the ids of their source
+    keys: HashMap<String, [u8; 32]>,
+}
+
+impl StaticKeys {
+    #[must_use]
+    pub fn new() -> StaticKeys {
+        StaticKeys::default()
+    }
+
+    /// Encrypt the topic or RPC method with the key
+    ///
+    /// # Errors
+    /// Will return `Err` if the `UUri` can't be turned into a Zenoh key
+    pub fn insert(&mut self, uri: &UUri, key: [u8; 32]) -> Result<(), UStatus> {
+        self.keys.insert(ULinkZenoh::to_zenoh_key_string(uri)?, key);
+        Ok(())
+    }
+}
+
+impl KeyProvider for StaticKeys {
+    fn key(&self, uri: &UUri) -> Option<[u8; 32]> {
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(uri).ok()?;
+        self.keys.get(&zenoh_key).copied()
+    }
+}
+
+// The attributes which are encrypted with the payload
+#[derive(Clone, PartialEq, Message)]
+struct Sealed {
+    #[prost(bytes = "vec", tag = "1")]
+    payload: Vec<u8>,
+    #[prost(string, optional, tag = "2")]
+    token: Option<String>,
+    #[prost(int32, optional, tag = "3")]
+    permission_level: Option<i32>,
+}
+
+// The topic, the attributes left in clear and the payload format are authenticated
+fn associated_data(zenoh_key: &str, format: i32, attributes: &UAttributes) -> Vec<u8> {
+    let mut aad = zenoh_key.as_bytes().to_vec();
+    aad.push(0);
+    aad.extend(format.to_be_bytes());
+    aad.extend(codec::encode_attributes(attributes));
+    aad
+}
+
+fn permission_denied(message: &str) -> UStatus {
+    UStatus::fail_with_code(UCode::PermissionDenied, message)
+}
+
+/// Encrypt the message if there is a key for the topic or RPC method
+pub(crate) fn seal(
+    keys: Option<&dyn KeyProvider>,
+    uri: &UUri,
+    payload: UPayload,
+    mut attributes: UAttributes,
+) -> Result<(UPayload, UAttributes), UStatus> {
+    let Some(key) = keys.and_then(|keys| keys.key(uri)) else {
+        return Ok((payload, attributes));
+    };
+    let zenoh_key = ULinkZenoh::to_zenoh_key_string(uri)?;
+    let Some(Data::Value(buf)) = payload.data else {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "Only payloads with a value can be encrypted",
+        ));
+    };
+    let sealed = Sealed {
+        payload: buf,
+        token: attributes.token.take(),
+        permission_level: attributes.permission_level.take(),
+    };
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: &sealed.encode_to_vec(),
+                aad: &associated_data(&zenoh_key, payload.format, &attributes),
+            },
+        )
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to encrypt the message"))?;
+    let mut data = nonce.to_vec();
+    data.extend(ciphertext);
+    Ok((
+        UPayload {
+            length: Some(0),
+            format: payload.format,
+            data: Some(Data::Value(data)),
+        },
+        attributes,
+    ))
+}
+
+/// Decrypt the message if there is a key for its source
+///
+/// # Errors
+/// Will return `Err` with `PermissionDenied` if unable to decrypt it with the key
+pub(crate) fn open(keys: Option<&dyn KeyProvider>, msg: UMessage) -> Result<UMessage, UStatus> {
+    let Some(key) = keys.and_then(|keys| msg.source.as_ref().and_then(|uri| keys.key(uri))) else {
+        return Ok(msg);
+    };
+    let UMessage {
+        source,
+        attributes,
+        payload,
+    } = msg;
+    let zenoh_key = source
+        .as_ref()
+        .map(ULinkZenoh::to_zenoh_key_string)
+        .transpose()?
+        .unwrap_or_default();
+    let (Some(mut attributes), Some(payload)) = (attributes, payload) else {
+        return Err(permission_denied("The message isn't encrypted"));
+    };
+    let Some(Data::Value(buf)) = &payload.data else {
+        return Err(permission_denied("The message isn't encrypted"));
+    };
+    if buf.len() < NONCE_LEN {
+        return Err(permission_denied("The message isn't encrypted"));
+    }
+    let (nonce, ciphertext) = buf.split_at(NONCE_LEN);
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &associated_data(&zenoh_key, payload.format, &attributes),
+            },
+        )
+        .map_err(|_| permission_denied("Unable to decrypt the message"))?;
+    let sealed = Sealed::decode(plaintext.as_slice())
+        .map_err(|_| permission_denied("Unable to decode the decrypted message"))?;
+    attributes.token = sealed.token;
+    attributes.permission_level = sealed.permission_level;
+    Ok(UMessage {
+        source,
+        attributes: Some(attributes),
+        payload: Some(UPayload {
+            length: Some(0),
+            format: payload.format,
+            data: Some(Data::Value(sealed.payload)),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::uprotocol::{UEntity, UPayloadFormat, UResource};
+
+    fn topic() -> UUri {
+        UUri {
+            entity: Some(UEntity {
+                name: "body.access".to_string(),
+                version_major: Some(1),
+                id: Some(1234),
+                ..Default::default()
+            }),
+            resource: Some(UResource {
+                name: "door".to_string(),
+                instance: Some("front_left".to_string()),
+                message: Some("Door".to_string()),
+                id: Some(5678),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn sealed_message(keys: &StaticKeys) -> UMessage {
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(b"Hello World!".to_vec())),
+        };
+        let attributes = UAttributes {
+            token: Some("secret".to_string()),
+            ttl: Some(1000),
+            ..Default::default()
+        };
+        let (payload, attributes) = seal(Some(keys), &topic(), payload, attributes).unwrap();
+        UMessage {
+            source: Some(topic()),
+            attributes: Some(attributes),
+            payload: Some(payload),
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open() {
+        let mut keys = StaticKeys::new();
+        keys.insert(&topic(), [7; 32]).unwrap();
+        let msg = sealed_message(&keys);
+        assert_eq!(msg.attributes.as_ref().unwrap().token, None);
+        assert_ne!(
+            msg.payload.as_ref().unwrap().data,
+            Some(Data::Value(b"Hello World!".to_vec()))
+        );
+
+        let msg = open(Some(&keys), msg).unwrap();
+        assert_eq!(
+            msg.attributes.as_ref().unwrap().token,
+            Some("secret".to_string())
+        );
+        assert_eq!(
+            msg.payload.unwrap().data,
+            Some(Data::Value(b"Hello World!".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_open_denied() {
+        let mut keys = StaticKeys::new();
+        keys.insert(&topic(), [7; 32]).unwrap();
+
+        // Wrong key
+        let mut other_keys = StaticKeys::new();
+        other_keys.insert(&topic(), [8; 32]).unwrap();
+        let status = open(Some(&other_keys), sealed_message(&keys)).unwrap_err();
+        assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+        // Tampered attributes
+        let mut msg = sealed_message(&keys);
+        msg.attributes.as_mut().unwrap().ttl = Some(2000);
+        let status = open(Some(&keys), msg).unwrap_err();
+        assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+        // Replayed on another topic with the same key
+        let mut other_topic = topic();
+        other_topic.resource.as_mut().unwrap().id = Some(5679);
+        keys.insert(&other_topic, [7; 32]).unwrap();
+        let mut msg = sealed_message(&keys);
+        msg.source = Some(other_topic);
+        let status = open(Some(&keys), msg).unwrap_err();
+        assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+        // Sent in clear
+        let msg = UMessage {
+            source: Some(topic()),
+            attributes: Some(UAttributes::default()),
+            payload: Some(UPayload {
+                length: Some(0),
+                format: UPayloadFormat::UpayloadFormatText as i32,
+                data: Some(Data::Value(b"Hello World!".to_vec())),
+            }),
+        };
+        let status = open(Some(&keys), msg).unwrap_err();
+        assert_eq!(status.code, UCode::PermissionDenied as i32);
+    }
+}
@@ -0,0 +1,365 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! uDiscovery service on top of Zenoh.
+//!
+//! Each [`UDiscoveryService`] answers the Zenoh queries on `up/discovery/<name>` with the
+//! `UUri`s of the uEntities it hosts, so a lookup reaches every `ULinkZenoh` without any central
+//! registry. The `LookupUri` RPC method is served on top of the same lookup.
+//!
+//! Only `LookupUri` is implemented, resolving a name and an optional major version into the
+//! `UUri`s of the uEntities. The other uDiscovery methods, e.g. looking up their resources,
+//! aren't.
+use crate::{codec, handler, ULinkZenoh};
+use prost::Message;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use uprotocol_sdk::{
+    rpc::RpcServer,
+    transport::{builder::UAttributesBuilder, datamodel::UTransport},
+    uprotocol::{UAuthority, UCode, UEntity, UPayload, UPayloadFormat, UPriority, UStatus, UUri},
+    uri::builder::resourcebuilder::UResourceBuilder,
+    uuid::builder::UUIDv8Builder,
+};
+use zenoh::{
+    prelude::{r#async::AsyncResolve, sync::SyncResolve, *},
+    queryable::{Query, Queryable},
+};
+
+pub const UDISCOVERY_NAME: &str = "core.udiscovery";
+pub const UDISCOVERY_ID: u32 = 1;
+pub const UDISCOVERY_VERSION: u32 = 3;
+pub const LOOKUP_URI_ID: u32 = 1;
+
+const DISCOVERY_PREFIX: &str = "up/discovery";
+// How long `LookupUri` waits for the hosts, well within the TTL of its requests
+const LOOKUP_TIMEOUT: Duration = Duration::from_millis(500);
+// TTL of the `LookupUri` requests of the client, in milliseconds
+const LOOKUP_URI_TTL: i32 = 2000;
+
+/// Batch of `UUri`, as defined in `uprotocol/uri.proto`
+#[derive(Clone, PartialEq, Message)]
+pub struct UUriBatch {
+    #[prost(message, repeated, tag = "1")]
+    pub uris: Vec<UUri>,
+}
+
+/// Response of `LookupUri`, as defined in `core/udiscovery/v3/udiscovery.proto`
+#[derive(Clone, PartialEq, Message)]
+pub struct LookupUriResponse {
+    #[prost(message, optional, tag = "1")]
+    pub uris: Option<UUriBatch>,
+    #[prost(message, optional, tag = "2")]
+    pub status: Option<UStatus>,
+}
+
+/// `UUri` of the uDiscovery `LookupUri` RPC method
+#[must_use]
+pub fn lookup_uri_method() -> UUri {
+    UUri {
+        entity: Some(UEntity {
+            name: UDISCOVERY_NAME.to_string(),
+            id: Some(UDISCOVERY_ID),
+            version_major: Some(UDISCOVERY_VERSION),
+            ..Default::default()
+        }),
+        resource: Some(UResourceBuilder::for_rpc_request(
+            Some("LookupUri".to_string()),
+            Some(LOOKUP_URI_ID),
+        )),
+        ..Default::default()
+    }
+}
+
+fn discovery_key(name: &str) -> Result<String, UStatus> {
+    if name.is_empty() || name.contains(|c| matches!(c, '/' | '*' | '$' | '?' | '#')) {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The uEntity name can't be used in a Zenoh key",
+        ));
+    }
+    Ok(format!("{DISCOVERY_PREFIX}/{name}"))
+}
+
+/// Find the uEntities with this name hosted anywhere on the Zenoh network
+///
+/// # Errors
+/// Will return `Err` if the name is invalid or if unable to query with Zenoh
+pub async fn find(ulink: &ULinkZenoh, name: &str) -> Result<Vec<UUri>, UStatus> {
+    let replies = ulink
+        .session
+        .get(&discovery_key(name)?)
+        .consolidation(ConsolidationMode::None)
+        .timeout(LOOKUP_TIMEOUT)
+        .res_async()
+        .await
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to query with Zenoh"))?;
+    let mut uris = vec![];
+    while let Ok(reply) = replies.recv_async().await {
+        let Ok(sample) = reply.sample else {
+            continue;
+        };
+        let Ok(uri) = UUri::decode(&*sample.payload.contiguous()) else {
+            continue;
+        };
+        if !uris.contains(&uri) {
+            uris.push(uri);
+        }
+    }
+    Ok(uris)
+}
+
+/// Serve the uDiscovery service for the uEntities hosted by a `ULinkZenoh`
+pub struct UDiscoveryService {
+    ulink: Arc<ULinkZenoh>,
+    authority: Option<UAuthority>,
+    hosted: Arc<Mutex<Vec<UUri>>>,
+    _queryable: Queryable<'static, ()>,
+    lookup_listener: String,
+}
+
+impl UDiscoveryService {
+    /// Start answering the discovery queries and serving `LookupUri`
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to declare the queryable or to register the RPC listener
+    pub async fn start(
+        ulink: &Arc<ULinkZenoh>,
+        authority: Option<UAuthority>,
+    ) -> Result<UDiscoveryService, UStatus> {
+        let hosted: Arc<Mutex<Vec<UUri>>> = Arc::new(Mutex::new(vec![]));
+
+        // Answer for the hosted uEntities
+        let hosted_cloned = hosted.clone();
+        let queryable = ulink
+            .session
+            .declare_queryable(format!("{DISCOVERY_PREFIX}/*"))
+            .callback(move |query: Query| {
+                let uris: Vec<UUri> = hosted_cloned.lock().unwrap().clone();
+                for uri in uris {
+                    let Some(entity) = &uri.entity else {
+                        continue;
+                    };
+                    let Ok(key) = discovery_key(&entity.name) else {
+                        continue;
+                    };
+                    let Ok(key_expr) = KeyExpr::try_from(key) else {
+                        continue;
+                    };
+                    if !query.key_expr().intersects(&key_expr) {
+                        continue;
+                    }
+                    let value = Value::new(uri.encode_to_vec().into()).encoding(codec::encoding(
+                        UPayloadFormat::UpayloadFormatProtobuf as i32,
+                    ));
+                    let _ = query.reply(Ok(Sample::new(key_expr, value))).res_sync();
+                }
+            })
+            .res_async()
+            .await
+            .map_err(|_| {
+                UStatus::fail_with_code(UCode::Internal, "Unable to declare discovery queryable")
+            })?;
+
+        // Serve LookupUri
+        let lookup_listener = handler::register_rpc_handler(
+            ulink,
+            lookup_uri_method(),
+            |ulink, payload| async move {
+                Ok(handler::protobuf_payload(&lookup(&ulink, payload).await))
+            },
+        )
+        .await?;
+
+        Ok(UDiscoveryService {
+            ulink: ulink.clone(),
+            authority,
+            hosted,
+            _queryable: queryable,
+            lookup_listener,
+        })
+    }
+
+    /// Make the uEntity discoverable, and declare its liveliness
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity can't be declared
+    pub async fn host(&self, entity: UEntity) -> Result<(), UStatus> {
+        discovery_key(&entity.name)?;
+        self.ulink.declare_entity(&entity).await?;
+        let uri = UUri {
+            authority: self.authority.clone(),
+            entity: Some(entity),
+            ..Default::default()
+        };
+        let mut hosted = self.hosted.lock().unwrap();
+        if !hosted.contains(&uri) {
+            hosted.push(uri);
+        }
+        Ok(())
+    }
+
+    /// Stop making the uEntity discoverable
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity isn't hosted
+    pub fn unhost(&self, entity: &UEntity) -> Result<(), UStatus> {
+        self.ulink.undeclare_entity(entity)?;
+        self.hosted
+            .lock()
+            .unwrap()
+            .retain(|uri| uri.entity.as_ref() != Some(entity));
+        Ok(())
+    }
+
+    /// Stop serving the uDiscovery service, and undeclare the liveliness of the hosted uEntities
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to unregister the RPC listener
+    pub async fn stop(self) -> Result<(), UStatus> {
+        let hosted = std::mem::take(&mut *self.hosted.lock().unwrap());
+        for entity in hosted.iter().filter_map(|uri| uri.entity.as_ref()) {
+            // An entity already undeclared through the uLink has nothing left to undeclare
+            let _ = self.ulink.undeclare_entity(entity);
+        }
+        self.ulink
+            .unregister_rpc_listener(lookup_uri_method(), &self.lookup_listener)
+            .await
+    }
+}
+
+// The request is a `UUri` whose uEntity has the name, and optionally the major version, to look up
+async fn lookup(ulink: &ULinkZenoh, payload: Option<UPayload>) -> LookupUriResponse {
+    let request: Option<UUri> = handler::decode_payload(payload);
+    let Some(entity) = request.and_then(|uri| uri.entity) else {
+        return LookupUriResponse {
+            uris: None,
+            status: Some(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "The request should be a UUri with a uEntity",
+            )),
+        };
+    };
+    match find(ulink, &entity.name).await {
+        Ok(mut uris) => {
+            if let Some(version_major) = entity.version_major {
+                uris.retain(|uri| {
+                    uri.entity.as_ref().and_then(|e| e.version_major) == Some(version_major)
+                });
+            }
+            let status = if uris.is_empty() {
+                UStatus::fail_with_code(UCode::NotFound, "uEntity not found")
+            } else {
+                UStatus {
+                    code: UCode::Ok as i32,
+                    ..Default::default()
+                }
+            };
+            LookupUriResponse {
+                uris: Some(UUriBatch { uris }),
+                status: Some(status),
+            }
+        }
+        Err(status) => LookupUriResponse {
+            uris: None,
+            status: Some(status),
+        },
+    }
+}
+
+/// Client of the uDiscovery `LookupUri` RPC method
+pub struct UDiscoveryClient<'a> {
+    ulink: &'a ULinkZenoh,
+}
+
+impl<'a> UDiscoveryClient<'a> {
+    #[must_use]
+    pub fn new(ulink: &'a ULinkZenoh) -> Self {
+        UDiscoveryClient { ulink }
+    }
+
+    /// Look up the uEntities with this name, and this major version if given
+    ///
+    /// # Errors
+    /// Will return `Err` if the RPC call fails, or with `NotFound` if there is no such uEntity
+    pub async fn lookup_uri(
+        &self,
+        name: &str,
+        version_major: Option<u32>,
+    ) -> Result<Vec<UUri>, UStatus> {
+        let method = lookup_uri_method();
+        let request = UUri {
+            entity: Some(UEntity {
+                name: name.to_string(),
+                version_major,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let attributes =
+            UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), LOOKUP_URI_TTL)
+                .with_reqid(UUIDv8Builder::new().build())
+                .build();
+        let payload = handler::protobuf_payload(&request);
+        let response = self
+            .ulink
+            .invoke_method_message(method, payload, attributes)
+            .await?;
+        let response: LookupUriResponse =
+            handler::decode_payload(handler::response_payload(response)?).ok_or(
+                UStatus::fail_with_code(UCode::Internal, "Unable to decode LookupUri response"),
+            )?;
+        match response.status {
+            Some(status) if status.code != UCode::Ok as i32 => Err(status),
+            _ => Ok(response.uris.map(|batch| batch.uris).unwrap_or_default()),
+        }
+    }
+
+    /// Resolve the name into the `UUri` of the uEntity with the highest major version
+    ///
+    /// # Errors
+    /// Same as [`UDiscoveryClient::lookup_uri`]
+    pub async fn resolve(&self, name: &str) -> Result<UUri, UStatus> {
+        self.lookup_uri(name, None)
+            .await?
+            .into_iter()
+            .max_by_key(|uri| uri.entity.as_ref().and_then(|e| e.version_major))
+            .ok_or(UStatus::fail_with_code(
+                UCode::NotFound,
+                "uEntity not found",
+            ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_uri_method() {
+        assert_eq!(
+            ULinkZenoh::to_zenoh_key_string(&lookup_uri_method()).unwrap(),
+            "0100000100010300"
+        );
+    }
+
+    #[test]
+    fn test_discovery_key() {
+        assert_eq!(
+            discovery_key("body.access").unwrap(),
+            "up/discovery/body.access"
+        );
+        assert!(discovery_key("body/access").is_err());
+        assert!(discovery_key("").is_err());
+    }
+}
@@ -0,0 +1,171 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! HTTP gateway, to publish and receive uProtocol messages as CloudEvents.
+//!
+//! * `POST /events` takes a CloudEvent in binary or structured mode, see [`crate::cloudevent`].
+//!   A `pub.v1` event is published and answered with `202 Accepted`. A `req.v1` event invokes
+//!   its `sink` method, and is answered with the response CloudEvent.
+//! * `GET /events?topic=<UUri>` streams the messages of the topic, or of the topics matching the
+//!   pattern (in the forms of [`crate::uri`]), as server-sent events holding structured
+//!   CloudEvents.
+use crate::{cloudevent, uri, ULinkZenoh};
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    routing::post,
+    Router,
+};
+use cloudevents::Event;
+use futures::{Stream, StreamExt};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use uprotocol_sdk::{
+    transport::datamodel::UTransport,
+    uprotocol::{Data, UCode, UMessage, UMessageType, UPayload, UStatus},
+};
+
+/// Routes of the gateway, to be served with `axum::Server`
+pub fn router(ulink: Arc<ULinkZenoh>) -> Router {
+    Router::new()
+        .route("/events", post(send_event).get(subscribe))
+        .with_state(ulink)
+}
+
+// The HTTP status closest to the UCode
+fn error_response(status: &UStatus) -> Response {
+    let code = match UCode::try_from(status.code) {
+        Ok(UCode::InvalidArgument | UCode::FailedPrecondition | UCode::OutOfRange) => {
+            StatusCode::BAD_REQUEST
+        }
+        Ok(UCode::Unauthenticated) => StatusCode::UNAUTHORIZED,
+        Ok(UCode::PermissionDenied) => StatusCode::FORBIDDEN,
+        Ok(UCode::NotFound) => StatusCode::NOT_FOUND,
+        Ok(UCode::Unavailable) => StatusCode::SERVICE_UNAVAILABLE,
+        Ok(UCode::DeadlineExceeded) => StatusCode::GATEWAY_TIMEOUT,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    (code, status.message.clone().unwrap_or_default()).into_response()
+}
+
+async fn send_event(State(ulink): State<Arc<ULinkZenoh>>, event: Event) -> Response {
+    let msg = match cloudevent::cloudevent_to_umessage(&event) {
+        Ok(msg) => msg,
+        Err(status) => return error_response(&status),
+    };
+    let UMessage {
+        source: Some(source),
+        attributes: Some(attributes),
+        payload,
+    } = msg
+    else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let payload = payload.unwrap_or(UPayload {
+        length: Some(0),
+        data: Some(Data::Value(vec![])),
+        ..Default::default()
+    });
+
+    match UMessageType::try_from(attributes.r#type) {
+        Ok(UMessageType::UmessageTypePublish) => {
+            match ulink.send(source, payload, attributes).await {
+                Ok(()) => StatusCode::ACCEPTED.into_response(),
+                Err(status) => error_response(&status),
+            }
+        }
+        Ok(UMessageType::UmessageTypeRequest) => {
+            // The source of a request is where the caller expects the response
+            let method = attributes.sink.clone().unwrap_or(source);
+            let response = match ulink
+                .invoke_method_message(method, payload, attributes)
+                .await
+            {
+                Ok(response) => response,
+                Err(status) => return error_response(&status),
+            };
+            match cloudevent::umessage_to_cloudevent(&response) {
+                Ok(event) => event.into_response(),
+                Err(_) => StatusCode::BAD_GATEWAY.into_response(),
+            }
+        }
+        _ => (
+            StatusCode::BAD_REQUEST,
+            "Only pub.v1 and req.v1 CloudEvents can be sent",
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Deserialize)]
+struct SubscribeQuery {
+    topic: String,
+}
+
+/// Server-sent events of a subscription, unregistering its listener once the client is gone
+struct Subscription {
+    events: flume::r#async::RecvStream<'static, Result<SseEvent, Infallible>>,
+    ulink: Arc<ULinkZenoh>,
+    listener: String,
+}
+
+impl Stream for Subscription {
+    type Item = Result<SseEvent, Infallible>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.events.poll_next_unpin(cx)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = self.ulink.unregister_pattern_listener(&self.listener);
+    }
+}
+
+async fn subscribe(
+    State(ulink): State<Arc<ULinkZenoh>>,
+    Query(query): Query<SubscribeQuery>,
+) -> Result<Sse<Subscription>, Response> {
+    let pattern =
+        uri::parse_uri(&query.topic).map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+    let (sender, receiver) = flume::unbounded();
+    let listener = ulink
+        .register_pattern_listener(
+            &pattern,
+            Box::new(move |result| {
+                let Ok(event) = result.and_then(|msg| cloudevent::umessage_to_cloudevent(&msg))
+                else {
+                    return;
+                };
+                if let Ok(event) = SseEvent::default().event("cloudevent").json_data(&event) {
+                    let _ = sender.send(Ok(event));
+                }
+            }),
+        )
+        .await
+        .map_err(|status| error_response(&status))?;
+    Ok(Sse::new(Subscription {
+        events: receiver.into_stream(),
+        ulink,
+        listener,
+    })
+    .keep_alive(KeepAlive::default()))
+}
@@ -0,0 +1,109 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use crate::{rt, ULinkZenoh};
+use prost::Message;
+use std::future::Future;
+use std::sync::Arc;
+use uprotocol_sdk::{
+    rpc::RpcServer,
+    transport::datamodel::UTransport,
+    uprotocol::{Data, UCode, UMessage, UMessageType, UPayload, UPayloadFormat, UStatus, UUri},
+};
+
+/// Serve the RPC method with an async handler.
+///
+/// The listener can't block, so the handler is run in a task which sends back the response.
+/// A handler error is sent back as a response holding the `UStatus`, with its code as `commstatus`.
+/// The handler only keeps a weak reference to the uLink, to avoid a cycle through the queryable.
+pub(crate) async fn register_rpc_handler<F, Fut>(
+    ulink: &Arc<ULinkZenoh>,
+    method: UUri,
+    handler: F,
+) -> Result<String, UStatus>
+where
+    F: Fn(Arc<ULinkZenoh>, Option<UPayload>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Result<UPayload, UStatus>> + Send + 'static,
+{
+    let weak = Arc::downgrade(ulink);
+    ulink
+        .register_rpc_listener(
+            method,
+            Box::new(move |result: Result<UMessage, UStatus>| {
+                let (Ok(msg), Some(ulink)) = (result, weak.upgrade()) else {
+                    return;
+                };
+                let UMessage {
+                    source,
+                    attributes,
+                    payload,
+                } = msg;
+                let (Some(source), Some(mut attributes)) = (source, attributes) else {
+                    return;
+                };
+                let response = handler(ulink.clone(), payload);
+                let task = async move {
+                    let (payload, commstatus) = match response.await {
+                        Ok(payload) => (payload, None),
+                        Err(status) => (protobuf_payload(&status), Some(status.code)),
+                    };
+                    attributes.set_type(UMessageType::UmessageTypeResponse);
+                    attributes.commstatus = commstatus;
+                    let _ = ulink.send(source, payload, attributes).await;
+                };
+                // The handler and its response continue the trace of the request
+                #[cfg(feature = "tracing")]
+                if let Some(ctx) = crate::trace::TraceContext::current() {
+                    rt::spawn(crate::trace::with_context(ctx, task));
+                    return;
+                }
+                rt::spawn(task);
+            }),
+        )
+        .await
+}
+
+/// Payload of the response, or the `UStatus` sent back by a failing handler
+pub(crate) fn response_payload(response: UMessage) -> Result<Option<UPayload>, UStatus> {
+    let commstatus = response
+        .attributes
+        .as_ref()
+        .and_then(|attributes| attributes.commstatus)
+        .filter(|code| *code != UCode::Ok as i32);
+    match commstatus {
+        Some(code) => Err(decode_payload(response.payload).unwrap_or_else(|| {
+            UStatus::fail_with_code(
+                UCode::try_from(code).unwrap_or(UCode::Unknown),
+                "The RPC call failed",
+            )
+        })),
+        None => Ok(response.payload),
+    }
+}
+
+/// Payload holding the protobuf message
+pub(crate) fn protobuf_payload<M: Message>(message: &M) -> UPayload {
+    UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatProtobuf as i32,
+        data: Some(Data::Value(message.encode_to_vec())),
+    }
+}
+
+/// Decode the protobuf message held by the payload
+pub(crate) fn decode_payload<M: Message + Default>(payload: Option<UPayload>) -> Option<M> {
+    match payload?.data? {
+        Data::Value(buf) => M::decode(buf.as_slice()).ok(),
+        Data::Reference(_) => None,
+    }
+}
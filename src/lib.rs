@@ -11,17 +11,51 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
+pub mod acl;
+pub mod blocking;
+mod cache;
+#[cfg(feature = "tools")]
+pub mod cli;
+pub mod cloudevent;
+pub mod codec;
+#[cfg(any(test, feature = "testing"))]
+pub mod conformance;
+pub mod crypto;
+pub mod discovery;
+#[cfg(feature = "gateway")]
+pub mod gateway;
+mod handler;
+pub mod liveliness;
+pub mod metrics;
+#[cfg(feature = "tools")]
+pub mod mock;
+pub mod record;
+pub mod rt;
+pub mod signing;
+pub mod storage;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod trace;
+pub mod uri;
+pub mod usubscription;
+
+use acl::{AccessControl, Action};
 use async_trait::async_trait;
-use prost::Message;
+use cache::PublicationCache;
+use cloudevents::Event;
+use crypto::KeyProvider;
+use liveliness::{AliveKind, AliveResource, LivelinessWatcher};
+use metrics::{MetricsSnapshot, TopicMetrics, ULinkMetrics};
+use signing::{SigningKeys, TrustStore};
 use std::collections::HashMap;
 use std::sync::{
     atomic::{AtomicU64, Ordering},
     Arc, Mutex,
 };
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use uprotocol_sdk::{
     rpc::{RpcClient, RpcClientResult, RpcMapperError, RpcServer},
-    transport::{datamodel::UTransport, validator::Validators},
+    transport::{builder::UAttributesBuilder, datamodel::UTransport, validator::Validators},
     uprotocol::{
         Data, UAttributes, UCode, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat,
         UPriority, UStatus, UUri, Uuid,
@@ -30,22 +64,46 @@ use uprotocol_sdk::{
         serializer::{MicroUriSerializer, UriSerializer},
         validator::UriValidator,
     },
+    uuid::builder::UUIDv8Builder,
 };
 use zenoh::{
     config::Config,
+    liveliness::LivelinessToken,
     prelude::{r#async::*, Sample},
     queryable::{Query, Queryable},
     sample::AttachmentBuilder,
     subscriber::Subscriber,
 };
 
+// Time to wait for the storages and publication caches to answer `get_latest`
+const GET_LATEST_TIMEOUT: Duration = Duration::from_millis(1000);
+// Time to wait for the response of a request without TTL
+const DEFAULT_RPC_TIMEOUT: Duration = Duration::from_millis(1000);
+
 pub struct ZenohListener {}
 pub struct ULinkZenoh {
     session: Arc<Session>,
     subscriber_map: Arc<Mutex<HashMap<String, Subscriber<'static, ()>>>>,
     queryable_map: Arc<Mutex<HashMap<String, Queryable<'static, ()>>>>,
     query_map: Arc<Mutex<HashMap<String, Query>>>,
+    // Liveliness tokens of the listeners, keyed by listener string
+    token_map: Arc<Mutex<HashMap<String, LivelinessToken<'static>>>>,
+    // Liveliness tokens of the local uEntities, keyed by liveliness key
+    entity_token_map: Arc<Mutex<HashMap<String, LivelinessToken<'static>>>>,
+    // Publication caches of the topics, keyed by Zenoh key
+    cache_map: Arc<Mutex<HashMap<String, PublicationCache>>>,
     callback_counter: AtomicU64,
+    metrics: Arc<ULinkMetrics>,
+    // Keys of the end-to-end encrypted topics and RPC methods
+    keys: Option<Arc<dyn KeyProvider>>,
+    // Keys signing the messages of the local uEntities
+    signing_keys: Option<Arc<SigningKeys>>,
+    // Keys and policies verifying the received messages
+    trust_store: Option<Arc<TrustStore>>,
+    // Policy of the local uEntity the uLink acts for
+    access: Option<Arc<AccessControl>>,
+    // Whether the samples without UAttributes, from plain Zenoh publishers, are delivered
+    foreign_publishers: bool,
 }
 
 impl ULinkZenoh {
@@ -58,15 +116,543 @@ impl ULinkZenoh {
                 "Unable to open Zenoh session",
             ));
         };
-        Ok(ULinkZenoh {
-            session: Arc::new(session),
+        Ok(ULinkZenoh::from_session(Arc::new(session)))
+    }
+
+    /// Create a uLink on an already opened Zenoh session.
+    /// The uLinks sharing a session reach each other without going through the network.
+    #[must_use]
+    pub fn from_session(session: Arc<Session>) -> ULinkZenoh {
+        ULinkZenoh {
+            session,
             subscriber_map: Arc::new(Mutex::new(HashMap::new())),
             queryable_map: Arc::new(Mutex::new(HashMap::new())),
             query_map: Arc::new(Mutex::new(HashMap::new())),
+            token_map: Arc::new(Mutex::new(HashMap::new())),
+            entity_token_map: Arc::new(Mutex::new(HashMap::new())),
+            cache_map: Arc::new(Mutex::new(HashMap::new())),
             callback_counter: AtomicU64::new(0),
+            metrics: Arc::new(ULinkMetrics::default()),
+            keys: None,
+            signing_keys: None,
+            trust_store: None,
+            access: None,
+            foreign_publishers: false,
+        }
+    }
+
+    /// Encrypt end to end the topics and RPC methods the provider has a key for, see
+    /// [`crypto`]. The listeners get the messages failing to decrypt as `PermissionDenied`.
+    #[must_use]
+    pub fn with_encryption(mut self, keys: Arc<dyn KeyProvider>) -> ULinkZenoh {
+        self.keys = Some(keys);
+        self
+    }
+
+    /// Sign the publications and responses of the uEntities there is a key for, and the requests
+    /// with the key of the requester, see [`signing`]
+    #[must_use]
+    pub fn with_signing(mut self, keys: Arc<SigningKeys>) -> ULinkZenoh {
+        self.signing_keys = Some(keys);
+        self
+    }
+
+    /// Verify the signature of the received publications, requests and responses, see [`signing`].
+    /// The listeners get the rejected messages as `Unauthenticated`.
+    #[must_use]
+    pub fn with_trust_store(mut self, trust_store: Arc<TrustStore>) -> ULinkZenoh {
+        self.trust_store = Some(trust_store);
+        self
+    }
+
+    /// Enforce the access policy of the local uEntity, see [`acl`].
+    /// The denied operations fail with `PermissionDenied`.
+    #[must_use]
+    pub fn with_access_control(mut self, access: Arc<AccessControl>) -> ULinkZenoh {
+        self.access = Some(access);
+        self
+    }
+
+    /// Deliver the samples published by plain Zenoh applications, which carry no `UAttributes`,
+    /// instead of failing them. Their `UAttributes` are made up: publish type, new id, and the
+    /// priority mapped back from the Zenoh one. The source is the topic, taken from the Zenoh key
+    /// by the pattern listeners.
+    #[must_use]
+    pub fn with_foreign_publishers(mut self) -> ULinkZenoh {
+        self.foreign_publishers = true;
+        self
+    }
+
+    // Check the access policy, if any
+    fn check_access(&self, action: Action, uri: &UUri) -> Result<(), UStatus> {
+        self.access
+            .as_ref()
+            .map_or(Ok(()), |access| access.check(action, uri))
+    }
+
+    /// Get the metrics collected since the creation of the uLink
+    #[must_use]
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Get the traffic counters of one topic, `None` if nothing was sent or received on it
+    ///
+    /// # Errors
+    /// Will return `Err` if the topic can't be transformed into a Zenoh key
+    pub fn topic_metrics(&self, topic: &UUri) -> Result<Option<TopicMetrics>, UStatus> {
+        Ok(self.metrics.topic(&ULinkZenoh::to_zenoh_key_string(topic)?))
+    }
+
+    /// Declare the liveliness of a local uEntity, until it's undeclared or the uLink is dropped
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity has no id, major version or valid name,
+    /// or if unable to declare the liveliness token
+    pub async fn declare_entity(&self, entity: &UEntity) -> Result<(), UStatus> {
+        let key = liveliness::entity_key(entity)?;
+        let token = self.declare_token(&key).await?;
+        self.entity_token_map.lock().unwrap().insert(key, token);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Will return `Err` if the uEntity wasn't declared
+    pub fn undeclare_entity(&self, entity: &UEntity) -> Result<(), UStatus> {
+        let key = liveliness::entity_key(entity)?;
+        if self.entity_token_map.lock().unwrap().remove(&key).is_none() {
+            return Err(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "Entity doesn't exist",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Keep the last `history` messages published on the topic by this uLink,
+    /// so the listeners registered with `register_querying_listener` get them when joining
+    ///
+    /// # Errors
+    /// Will return `Err` if the history depth is 0, if the topic is invalid, with
+    /// `PermissionDenied` if the access control doesn't allow to publish to it,
+    /// or if unable to declare the cache queryable
+    pub async fn enable_publication_cache(
+        &self,
+        topic: &UUri,
+        history: usize,
+    ) -> Result<(), UStatus> {
+        UriValidator::validate(topic)
+            .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
+        self.check_access(Action::Publish, topic)?;
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(topic)?;
+        let cache = PublicationCache::new(&self.session, &zenoh_key, history).await?;
+        self.cache_map.lock().unwrap().insert(zenoh_key, cache);
+        Ok(())
+    }
+
+    /// # Errors
+    /// Will return `Err` if the topic has no publication cache
+    pub fn disable_publication_cache(&self, topic: &UUri) -> Result<(), UStatus> {
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(topic)?;
+        if self.cache_map.lock().unwrap().remove(&zenoh_key).is_none() {
+            return Err(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "Publication cache doesn't exist",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Same as `register_listener`, but the listener first gets the messages kept by the
+    /// publication caches of the topic, in publication order
+    ///
+    /// # Errors
+    /// Same as [`UTransport::register_listener`]
+    pub async fn register_querying_listener(
+        &self,
+        topic: UUri,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        UriValidator::validate(&topic)
+            .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
+        self.check_access(Action::Subscribe, &topic)?;
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
+        let handler = Arc::new(ULinkZenoh::sample_handler(
+            Some(topic),
+            listener,
+            self.metrics.clone(),
+            self.keys.clone(),
+            self.trust_store.clone(),
+            self.foreign_publishers,
+        ));
+
+        // The live samples are held back until the cached ones are delivered
+        let pending: Arc<Mutex<Option<Vec<Sample>>>> = Arc::new(Mutex::new(Some(vec![])));
+        let pending_cloned = pending.clone();
+        let handler_cloned = handler.clone();
+        let hashmap_key = self
+            .declare_listener(&zenoh_key, true, move |sample: Sample| {
+                if let Some(samples) = pending_cloned.lock().unwrap().as_mut() {
+                    samples.push(sample);
+                    return;
+                }
+                handler_cloned(&sample);
+            })
+            .await?;
+
+        // Get the cached samples, without failing if there is no cache to answer
+        let mut cached = vec![];
+        if let Ok(replies) = self
+            .session
+            .get(&cache::cache_key(&zenoh_key))
+            .consolidation(ConsolidationMode::None)
+            .timeout(Duration::from_millis(1000))
+            .res()
+            .await
+        {
+            while let Ok(reply) = replies.recv_async().await {
+                if let Ok(sample) = reply.sample {
+                    cached.push(sample);
+                }
+            }
+        }
+        cached.sort_by_key(ULinkZenoh::sample_id);
+        cached.dedup_by_key(|sample| ULinkZenoh::sample_id(sample));
+        for sample in &cached {
+            handler(sample);
+        }
+
+        // Then the live ones, skipping those already in the caches
+        let cached_ids: Vec<_> = cached.iter().filter_map(ULinkZenoh::sample_id).collect();
+        loop {
+            let samples = {
+                let mut pending = pending.lock().unwrap();
+                match pending.as_mut() {
+                    Some(samples) if !samples.is_empty() => std::mem::take(samples),
+                    _ => {
+                        *pending = None;
+                        break;
+                    }
+                }
+            };
+            for sample in &samples {
+                if !ULinkZenoh::sample_id(sample).is_some_and(|id| cached_ids.contains(&id)) {
+                    handler(sample);
+                }
+            }
+        }
+
+        Ok(hashmap_key)
+    }
+
+    /// Register a listener of all the local topics matching the pattern, see
+    /// [`storage::topic_key_expr`]. The source of each message is the topic it was published on.
+    ///
+    /// The listener is unregistered with `unregister_pattern_listener`, as the pattern may not be
+    /// a valid topic. It declares no liveliness token, so the matching topics don't look listened.
+    ///
+    /// # Errors
+    /// Will return `Err` if the pattern is invalid or if unable to register the Zenoh subscriber
+    pub async fn register_pattern_listener(
+        &self,
+        pattern: &UUri,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        self.check_access(Action::Subscribe, pattern)?;
+        let key_expr = storage::topic_key_expr(pattern)?;
+        let handler = ULinkZenoh::sample_handler(
+            None,
+            listener,
+            self.metrics.clone(),
+            self.keys.clone(),
+            self.trust_store.clone(),
+            self.foreign_publishers,
+        );
+        self.declare_listener(&key_expr, false, move |sample: Sample| handler(&sample))
+            .await
+    }
+
+    /// Unregister a listener registered with `register_pattern_listener`
+    ///
+    /// # Errors
+    /// Will return `Err` if the listener doesn't exist
+    pub fn unregister_pattern_listener(&self, listener: &str) -> Result<(), UStatus> {
+        if !self.subscriber_map.lock().unwrap().contains_key(listener) {
+            return Err(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "Listener doesn't exist",
+            ));
+        }
+
+        self.subscriber_map.lock().unwrap().remove(listener);
+        self.token_map.lock().unwrap().remove(listener);
+        Ok(())
+    }
+
+    /// Register a listener receiving the messages of the topic as CloudEvents,
+    /// to be unregistered with `unregister_listener`
+    ///
+    /// # Errors
+    /// Will return `Err` if the topic is invalid or if unable to register the Zenoh subscriber
+    pub async fn register_cloudevent_listener(
+        &self,
+        topic: UUri,
+        listener: Box<dyn Fn(Result<Event, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        self.register_listener(
+            topic,
+            Box::new(move |result: Result<UMessage, UStatus>| {
+                listener(result.and_then(|msg| cloudevent::umessage_to_cloudevent(&msg)));
+            }),
+        )
+        .await
+    }
+
+    /// Get the latest message of the topic kept by a storage or a publication cache,
+    /// without listening to the topic
+    ///
+    /// # Errors
+    /// Will return `Err` if the topic is invalid, with `PermissionDenied` if the access control
+    /// doesn't allow to subscribe to it, if unable to query with Zenoh,
+    /// or with `NotFound` if no message is kept for the topic
+    pub async fn get_latest(&self, topic: UUri) -> Result<UMessage, UStatus> {
+        UriValidator::validate(&topic)
+            .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
+        self.check_access(Action::Subscribe, &topic)?;
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
+
+        // The storages answer on the topic key, the publication caches on the cache key
+        let mut receivers = vec![];
+        for key in [zenoh_key.clone(), cache::cache_key(&zenoh_key)] {
+            let replies = self
+                .session
+                .get(&key)
+                .consolidation(ConsolidationMode::None)
+                .timeout(GET_LATEST_TIMEOUT)
+                .res()
+                .await
+                .map_err(|_| {
+                    UStatus::fail_with_code(UCode::Internal, "Unable to query with Zenoh")
+                })?;
+            receivers.push(replies);
+        }
+        let mut latest: Option<(Option<(u64, u64)>, UMessage, usize)> = None;
+        for replies in receivers {
+            while let Ok(reply) = replies.recv_async().await {
+                let Ok(sample) = reply.sample else {
+                    continue;
+                };
+                let Ok(msg) =
+                    ULinkZenoh::sample_to_umessage(&topic, &sample, self.foreign_publishers)
+                        .and_then(|msg| {
+                            signing::verify(
+                                self.trust_store.as_deref(),
+                                &msg,
+                                sample.attachment(),
+                            )?;
+                            crypto::open(self.keys.as_deref(), msg)
+                        })
+                else {
+                    self.metrics.on_decode_failure();
+                    continue;
+                };
+                if msg.attributes.as_ref().is_some_and(ULinkZenoh::is_expired) {
+                    continue;
+                }
+                let id = ULinkZenoh::sample_id(&sample);
+                let newer = match &latest {
+                    Some((latest_id, _, _)) => id > *latest_id,
+                    None => true,
+                };
+                if newer {
+                    latest = Some((id, msg, sample.payload.len()));
+                }
+            }
+        }
+        let Some((_, msg, len)) = latest else {
+            return Err(UStatus::fail_with_code(
+                UCode::NotFound,
+                "No message kept for the topic",
+            ));
+        };
+        self.metrics.on_received(&zenoh_key, len);
+        Ok(msg)
+    }
+
+    /// Get the uEntities, RPC methods and topics currently alive on the Zenoh network
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to query the liveliness tokens
+    pub async fn alive(&self, kind: Option<AliveKind>) -> Result<Vec<AliveResource>, UStatus> {
+        let key_expr = kind.map_or_else(
+            || format!("{}/**", liveliness::LIVELINESS_PREFIX),
+            liveliness::kind_key_expr,
+        );
+        let replies = self
+            .session
+            .liveliness()
+            .get(&key_expr)
+            .res()
+            .await
+            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to query liveliness"))?;
+        let mut resources = vec![];
+        while let Ok(reply) = replies.recv_async().await {
+            if let Some(resource) = reply
+                .sample
+                .ok()
+                .and_then(|sample| liveliness::parse_key(sample.key_expr.as_str()))
+            {
+                if !resources.contains(&resource) {
+                    resources.push(resource);
+                }
+            }
+        }
+        Ok(resources)
+    }
+
+    /// Check whether the RPC method or the topic is currently served or listened
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to query the liveliness tokens
+    pub async fn is_alive(&self, kind: AliveKind, uri: &UUri) -> Result<bool, UStatus> {
+        let key = ULinkZenoh::alive_key(kind, uri)?;
+        let replies = self
+            .session
+            .liveliness()
+            .get(&key)
+            .res()
+            .await
+            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to query liveliness"))?;
+        while let Ok(reply) = replies.recv_async().await {
+            if reply.sample.is_ok() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Watch the uEntities, RPC methods and topics appearing or disappearing on the Zenoh network.
+    /// The resources already alive aren't notified, use `alive` to get them.
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to declare the liveliness subscriber
+    pub async fn watch_liveliness(
+        &self,
+        kind: Option<AliveKind>,
+    ) -> Result<LivelinessWatcher, UStatus> {
+        let key_expr = kind.map_or_else(
+            || format!("{}/**", liveliness::LIVELINESS_PREFIX),
+            liveliness::kind_key_expr,
+        );
+        let (sender, receiver) = flume::unbounded();
+        let subscriber = self
+            .session
+            .liveliness()
+            .declare_subscriber(&key_expr)
+            .callback(LivelinessWatcher::callback(sender))
+            .res()
+            .await
+            .map_err(|_| {
+                UStatus::fail_with_code(UCode::Internal, "Unable to declare liveliness subscriber")
+            })?;
+        Ok(LivelinessWatcher {
+            _subscriber: subscriber,
+            receiver,
         })
     }
 
+    /// Wait until the topic is listened somewhere on the Zenoh network, so that the messages sent
+    /// from now on are received
+    ///
+    /// Only the listeners declaring a liveliness token, those of `register_listener`, are seen.
+    /// The storages, the pattern listeners, `usniff` and the plain Zenoh subscribers aren't.
+    ///
+    /// # Errors
+    /// Will return `Err` with `DeadlineExceeded` if there is still no listener after the timeout,
+    /// or if unable to watch the liveliness tokens
+    pub async fn wait_for_listener(&self, topic: &UUri, timeout: Duration) -> Result<(), UStatus> {
+        self.wait_until_alive(AliveKind::Topic, topic, timeout)
+            .await
+    }
+
+    /// Wait until the RPC method is served somewhere on the Zenoh network, so that it can be
+    /// invoked
+    ///
+    /// Only the methods served with `register_rpc_listener` are seen, not the plain Zenoh
+    /// queryables.
+    ///
+    /// # Errors
+    /// Will return `Err` with `DeadlineExceeded` if the method still isn't served after the
+    /// timeout, or if unable to watch the liveliness tokens
+    pub async fn wait_for_rpc_server(
+        &self,
+        method: &UUri,
+        timeout: Duration,
+    ) -> Result<(), UStatus> {
+        self.wait_until_alive(AliveKind::Method, method, timeout)
+            .await
+    }
+
+    // Wait for the liveliness token of the resource, watching it before checking whether it
+    // already exists so that a token declared in between isn't missed
+    pub(crate) async fn wait_until_alive(
+        &self,
+        kind: AliveKind,
+        uri: &UUri,
+        timeout: Duration,
+    ) -> Result<(), UStatus> {
+        let key = ULinkZenoh::alive_key(kind, uri)?;
+        let (sender, receiver) = flume::bounded(1);
+        let _subscriber = self
+            .session
+            .liveliness()
+            .declare_subscriber(&key)
+            .callback(move |sample: Sample| {
+                if sample.kind == SampleKind::Put {
+                    let _ = sender.try_send(());
+                }
+            })
+            .res()
+            .await
+            .map_err(|_| {
+                UStatus::fail_with_code(UCode::Internal, "Unable to declare liveliness subscriber")
+            })?;
+        if self.is_alive(kind, uri).await? {
+            return Ok(());
+        }
+        match rt::timeout(timeout, receiver.recv_async()).await {
+            Some(Ok(())) => Ok(()),
+            _ => Err(UStatus::fail_with_code(
+                UCode::DeadlineExceeded,
+                "Not alive before the timeout",
+            )),
+        }
+    }
+
+    // Liveliness key of the uEntity, RPC method or topic
+    fn alive_key(kind: AliveKind, uri: &UUri) -> Result<String, UStatus> {
+        match kind {
+            AliveKind::Entity => liveliness::entity_key(uri.entity.as_ref().ok_or(
+                UStatus::fail_with_code(UCode::InvalidArgument, "The UUri has no uEntity"),
+            )?),
+            AliveKind::Method | AliveKind::Topic => Ok(liveliness::resource_key(
+                kind,
+                &ULinkZenoh::to_zenoh_key_string(uri)?,
+            )),
+        }
+    }
+
+    async fn declare_token(&self, key: &str) -> Result<LivelinessToken<'static>, UStatus> {
+        self.session
+            .liveliness()
+            .declare_token(key.to_string())
+            .res()
+            .await
+            .map_err(|_| {
+                UStatus::fail_with_code(UCode::Internal, "Unable to declare liveliness token")
+            })
+    }
+
     fn to_zenoh_key_string(uri: &UUri) -> Result<String, UStatus> {
         let micro_uuri = MicroUriSerializer::serialize(uri).map_err(|_| {
             UStatus::fail_with_code(
@@ -80,6 +666,24 @@ impl ULinkZenoh {
         Ok(micro_zenoh_key)
     }
 
+    fn from_zenoh_key_string(zenoh_key: &str) -> Result<UUri, UStatus> {
+        let invalid_key = || UStatus::fail_with_code(UCode::InvalidArgument, "Invalid Zenoh key");
+        if zenoh_key.len() % 2 != 0 || !zenoh_key.is_ascii() {
+            return Err(invalid_key());
+        }
+        let micro_uuri = (0..zenoh_key.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&zenoh_key[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid_key())?;
+        MicroUriSerializer::deserialize(micro_uuri).map_err(|_| {
+            UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "Unable to deserialize from micro format",
+            )
+        })
+    }
+
     #[allow(clippy::match_same_arms)]
     fn map_zenoh_priority(upriority: UPriority) -> Priority {
         match upriority {
@@ -96,16 +700,291 @@ impl ULinkZenoh {
         }
     }
 
+    fn map_upriority(priority: Priority) -> UPriority {
+        match priority {
+            Priority::Background => UPriority::UpriorityCs0,
+            Priority::DataLow => UPriority::UpriorityCs1,
+            Priority::Data => UPriority::UpriorityCs2,
+            Priority::DataHigh => UPriority::UpriorityCs3,
+            Priority::InteractiveLow => UPriority::UpriorityCs4,
+            Priority::InteractiveHigh => UPriority::UpriorityCs5,
+            Priority::RealTime | Priority::Control => UPriority::UpriorityCs6,
+        }
+    }
+
     // TODO: We need a standard way in uprotocol-rust to change UUID to String
     fn uuid_to_string(uuid: &Uuid) -> String {
         format!("{}:{}", uuid.msb, uuid.lsb)
     }
 
+    // The TTL of the request, or the default timeout without TTL
+    fn rpc_timeout(attributes: &UAttributes) -> Duration {
+        attributes
+            .ttl
+            .and_then(|ttl| u64::try_from(ttl).ok())
+            .filter(|ttl| *ttl > 0)
+            .map_or(DEFAULT_RPC_TIMEOUT, Duration::from_millis)
+    }
+
+    // The response holding the status of a request which didn't reach its listener, with the
+    // status code as commstatus
+    fn status_response(
+        method: &UUri,
+        mut attributes: UAttributes,
+        status: &UStatus,
+        keys: Option<&dyn KeyProvider>,
+        signing_keys: Option<&SigningKeys>,
+    ) -> Result<(Sample, AttachmentBuilder), UStatus> {
+        attributes.set_type(UMessageType::UmessageTypeResponse);
+        attributes.commstatus = Some(status.code);
+        let (payload, attributes) =
+            crypto::seal(keys, method, handler::protobuf_payload(status), attributes)?;
+        let attr = codec::encode_attributes(&attributes);
+        let signature = signing::sign(signing_keys, method, &payload, &attr, false)?;
+        let Some(Data::Value(buf)) = payload.data else {
+            return Err(UStatus::fail_with_code(UCode::Internal, "Invalid data"));
+        };
+        let mut attachment = codec::attachment(&attr);
+        if let Some(signature) = &signature {
+            signature.attach(&mut attachment);
+        }
+        let key_expr = KeyExpr::new(ULinkZenoh::to_zenoh_key_string(method)?)
+            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to create Zenoh key"))?;
+        let value = Value::new(buf.into()).encoding(codec::encoding(payload.format));
+        Ok((Sample::new(key_expr, value), attachment))
+    }
+
+    // The uProtocol UUID carries its creation time (Unix ms) in the 48 most significant bits
+    fn is_expired(attributes: &UAttributes) -> bool {
+        let (Some(id), Some(ttl)) = (&attributes.id, attributes.ttl) else {
+            return false;
+        };
+        let Ok(ttl) = u64::try_from(ttl) else {
+            return false;
+        };
+        if ttl == 0 {
+            return false;
+        }
+        let created = id.msb >> 16;
+        record::now_millis() > created.saturating_add(ttl)
+    }
+
+    // Rebuild the UMessage from a sample received by a subscriber.
+    // With foreign publishers, the UAttributes missing from the sample are made up.
+    fn sample_to_umessage(
+        topic: &UUri,
+        sample: &Sample,
+        foreign_publishers: bool,
+    ) -> Result<UMessage, UStatus> {
+        let foreign = foreign_publishers
+            && sample
+                .attachment()
+                .and_then(|attachment| attachment.get(&codec::UATTRIBUTES_KEY.as_bytes()))
+                .is_none();
+        if !foreign {
+            let (u_attribute, u_payload) = codec::decode_message(
+                &codec::attachment_raw_attributes(sample.attachment())?,
+                &sample.encoding,
+                &sample.payload.contiguous(),
+            )?;
+            return Ok(UMessage {
+                source: Some(topic.clone()),
+                attributes: Some(u_attribute),
+                payload: Some(u_payload),
+            });
+        }
+        let u_attribute = UAttributes {
+            id: Some(UUIDv8Builder::new().build()),
+            ..UAttributesBuilder::publish(ULinkZenoh::map_upriority(sample.qos.priority())).build()
+        };
+        // Whatever the encoding of a foreign publisher, the payload is still delivered
+        let u_payload = UPayload {
+            length: Some(0),
+            format: codec::decode_format(&sample.encoding)
+                .unwrap_or(UPayloadFormat::UpayloadFormatUnspecified as i32),
+            data: Some(Data::Value(sample.payload.contiguous().to_vec())),
+        };
+        Ok(UMessage {
+            source: Some(topic.clone()),
+            attributes: Some(u_attribute),
+            payload: Some(u_payload),
+        })
+    }
+
+    // The UUID of the message, to order and deduplicate the samples
+    fn sample_id(sample: &Sample) -> Option<(u64, u64)> {
+        codec::attachment_attributes(sample.attachment())
+            .ok()
+            .and_then(|attributes| attributes.id)
+            .map(|id| (id.msb, id.lsb))
+    }
+
+    // Decode the samples of a topic and pass them to the listener.
+    // Without topic, as for a pattern, the topic is taken from the Zenoh key of each sample.
+    fn sample_handler(
+        topic: Option<UUri>,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+        metrics: Arc<ULinkMetrics>,
+        keys: Option<Arc<dyn KeyProvider>>,
+        trust_store: Option<Arc<TrustStore>>,
+        foreign_publishers: bool,
+    ) -> impl Fn(&Sample) + Send + Sync + 'static {
+        move |sample: &Sample| {
+            #[cfg(feature = "tracing")]
+            let span = tracing::debug_span!(
+                "sample",
+                uuri = ?topic,
+                zenoh_key = %sample.key_expr,
+                id = tracing::field::Empty,
+                reqid = tracing::field::Empty,
+                trace_id = tracing::field::Empty,
+                parent_id = tracing::field::Empty,
+            )
+            .entered();
+            // Create UMessage
+            let msg = topic
+                .as_ref()
+                .map_or_else(
+                    || ULinkZenoh::from_zenoh_key_string(sample.key_expr.as_str()),
+                    |topic| Ok(topic.clone()),
+                )
+                .and_then(|topic| {
+                    ULinkZenoh::sample_to_umessage(&topic, sample, foreign_publishers)
+                })
+                .and_then(|msg| {
+                    signing::verify(trust_store.as_deref(), &msg, sample.attachment())?;
+                    crypto::open(keys.as_deref(), msg)
+                });
+            let msg = match msg {
+                Ok(msg) => msg,
+                Err(e) => {
+                    metrics.on_decode_failure();
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = ?e, "Unable to decode sample");
+                    listener(Err(e));
+                    return;
+                }
+            };
+            // The listener continues the trace of the message
+            #[cfg(feature = "tracing")]
+            let _context = {
+                let ctx = trace::extract(sample.attachment());
+                if let Some(u_attribute) = &msg.attributes {
+                    trace::record(&span, u_attribute, ctx);
+                }
+                ctx.map(trace::TraceContext::enter)
+            };
+            // Drop the message if its TTL is already expired
+            if msg.attributes.as_ref().is_some_and(ULinkZenoh::is_expired) {
+                metrics.on_ttl_drop();
+                #[cfg(feature = "tracing")]
+                tracing::debug!("Sample dropped for expired TTL");
+                listener(Err(UStatus::fail_with_code(
+                    UCode::DeadlineExceeded,
+                    "The message expired before being received",
+                )));
+                return;
+            }
+            metrics.on_received(sample.key_expr.as_str(), sample.payload.len());
+            #[cfg(feature = "tracing")]
+            tracing::debug!(bytes = sample.payload.len(), "Sample received");
+            listener(Ok(msg));
+        }
+    }
+
+    // Declare the subscriber of a listener and, if `alive`, its liveliness token, return the
+    // listener string
+    async fn declare_listener<C>(
+        &self,
+        zenoh_key: &str,
+        alive: bool,
+        callback: C,
+    ) -> Result<String, UStatus>
+    where
+        C: Fn(Sample) + Send + Sync + 'static,
+    {
+        // Generate listener string for users to delete
+        let hashmap_key = format!(
+            "{}_{:X}",
+            zenoh_key,
+            self.callback_counter.fetch_add(1, Ordering::SeqCst)
+        );
+
+        if let Ok(subscriber) = self
+            .session
+            .declare_subscriber(zenoh_key)
+            .callback(callback)
+            .res()
+            .await
+        {
+            self.subscriber_map
+                .lock()
+                .unwrap()
+                .insert(hashmap_key.clone(), subscriber);
+        } else {
+            return Err(UStatus::fail_with_code(
+                UCode::Internal,
+                "Unable to register callback with Zenoh",
+            ));
+        }
+
+        // A wildcard token would make every matching topic look listened
+        if !alive {
+            return Ok(hashmap_key);
+        }
+
+        // Tell the publishers the topic is listened, or don't listen to it
+        let token = match self
+            .declare_token(&liveliness::resource_key(AliveKind::Topic, zenoh_key))
+            .await
+        {
+            Ok(token) => token,
+            Err(e) => {
+                self.subscriber_map.lock().unwrap().remove(&hashmap_key);
+                return Err(e);
+            }
+        };
+        self.token_map
+            .lock()
+            .unwrap()
+            .insert(hashmap_key.clone(), token);
+
+        Ok(hashmap_key)
+    }
+
+    // Rebuild the UMessage from a query received by a queryable
+    fn query_to_umessage(method: &UUri, query: &Query) -> Result<UMessage, UStatus> {
+        let attributes = codec::attachment_raw_attributes(query.attachment())?;
+        let (u_attribute, u_payload) = match query.value() {
+            Some(value) => {
+                codec::decode_message(&attributes, &value.encoding, &value.payload.contiguous())?
+            }
+            None => (
+                codec::decode_attributes(&attributes)?,
+                UPayload {
+                    length: Some(0),
+                    format: UPayloadFormat::UpayloadFormatUnspecified as i32,
+                    data: None,
+                },
+            ),
+        };
+        Ok(UMessage {
+            source: Some(method.clone()),
+            attributes: Some(u_attribute),
+            payload: Some(u_payload),
+        })
+    }
+
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(zenoh_key = zenoh_key))
+    )]
     async fn send_publish(
         &self,
         zenoh_key: &str,
         payload: UPayload,
         attributes: UAttributes,
+        signature: Option<Vec<u8>>,
     ) -> Result<(), UStatus> {
         // Get the data from UPayload
         let Some(Data::Value(buf)) = payload.data else {
@@ -116,43 +995,62 @@ impl ULinkZenoh {
             ));
         };
 
+        let buf_len = buf.len();
+        // Keep a copy for the late joiners
+        let cached_buf = self
+            .cache_map
+            .lock()
+            .unwrap()
+            .contains_key(zenoh_key)
+            .then(|| buf.clone());
+
         // Serialized UAttributes into protobuf
         let priority = ULinkZenoh::map_zenoh_priority(attributes.priority());
-        let mut attr = vec![];
-        let Ok(()) = attributes.encode(&mut attr) else {
-            return Err(UStatus::fail_with_code(
-                UCode::InvalidArgument,
-                "Unable to encode UAttributes",
-            ));
-        };
+        let attr = codec::encode_attributes(&attributes);
 
         // Add attachment and payload
-        let mut attachment = AttachmentBuilder::new();
-        attachment.insert("uattributes", attr.as_slice());
+        let mut attachment = codec::attachment(&attr);
+        if let Some(signature) = &signature {
+            attachment.insert(signing::SIGNATURE_KEY, signature.as_slice());
+        }
+        #[cfg(feature = "tracing")]
+        trace::inject(&mut attachment, &attributes);
         let putbuilder = self
             .session
             .put(zenoh_key, buf)
-            .encoding(Encoding::WithSuffix(
-                KnownEncoding::AppCustom,
-                payload.format.to_string().into(),
-            ))
+            .encoding(codec::encoding(payload.format))
             .priority(priority)
             .with_attachment(attachment.build());
 
         // Send data
-        putbuilder
-            .res()
-            .await
-            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to send with Zenoh"))?;
+        putbuilder.res().await.map_err(|_| {
+            #[cfg(feature = "tracing")]
+            tracing::error!("Unable to send with Zenoh");
+            UStatus::fail_with_code(UCode::Internal, "Unable to send with Zenoh")
+        })?;
 
+        if let Some(buf) = cached_buf {
+            if let Some(cache) = self.cache_map.lock().unwrap().get(zenoh_key) {
+                cache.push(buf, payload.format, attr, signature);
+            }
+        }
+
+        self.metrics.on_sent(zenoh_key, buf_len);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = buf_len, "Publish sent");
         Ok(())
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(zenoh_key = zenoh_key, reqid = tracing::field::Empty))
+    )]
     async fn send_response(
         &self,
         zenoh_key: &str,
         payload: UPayload,
         attributes: UAttributes,
+        signature: Option<Vec<u8>>,
     ) -> Result<(), UStatus> {
         // Get the data from UPayload
         let Some(Data::Value(buf)) = payload.data else {
@@ -162,28 +1060,26 @@ impl ULinkZenoh {
                 "Invalid data",
             ));
         };
+        let buf_len = buf.len();
 
         // Serialized UAttributes into protobuf
-        let mut attr = vec![];
-        let Ok(()) = attributes.encode(&mut attr) else {
-            return Err(UStatus::fail_with_code(
-                UCode::InvalidArgument,
-                "Unable to encode UAttributes",
-            ));
-        };
+        let attr = codec::encode_attributes(&attributes);
         // Get reqid
-        let reqid = ULinkZenoh::uuid_to_string(&attributes.reqid.ok_or(
+        let reqid = ULinkZenoh::uuid_to_string(attributes.reqid.as_ref().ok_or(
             UStatus::fail_with_code(UCode::InvalidArgument, "reqid doesn't exist"),
         )?);
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("reqid", reqid.as_str());
 
         // Add attachment and payload
-        let mut attachment = AttachmentBuilder::new();
-        attachment.insert("uattributes", attr.as_slice());
+        let mut attachment = codec::attachment(&attr);
+        if let Some(signature) = &signature {
+            attachment.insert(signing::SIGNATURE_KEY, signature.as_slice());
+        }
+        #[cfg(feature = "tracing")]
+        trace::inject(&mut attachment, &attributes);
         // Send back query
-        let value = Value::new(buf.into()).encoding(Encoding::WithSuffix(
-            KnownEncoding::AppCustom,
-            payload.format.to_string().into(),
-        ));
+        let value = Value::new(buf.into()).encoding(codec::encoding(payload.format));
         let reply = Ok(Sample::new(
             KeyExpr::new(zenoh_key.to_string()).map_err(|_| {
                 UStatus::fail_with_code(UCode::Internal, "Unable to create Zenoh key")
@@ -195,10 +1091,11 @@ impl ULinkZenoh {
             .lock()
             .unwrap()
             .get(&reqid)
-            .ok_or(UStatus::fail_with_code(
-                UCode::Internal,
-                "query doesn't exist",
-            ))?
+            .ok_or_else(|| {
+                #[cfg(feature = "tracing")]
+                tracing::error!("query doesn't exist");
+                UStatus::fail_with_code(UCode::Internal, "query doesn't exist")
+            })?
             .clone();
 
         // Send data
@@ -209,109 +1106,203 @@ impl ULinkZenoh {
             .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to add attachment"))?
             .res()
             .await
-            .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to reply with Zenoh"))?;
+            .map_err(|_| {
+                #[cfg(feature = "tracing")]
+                tracing::error!("Unable to reply with Zenoh");
+                UStatus::fail_with_code(UCode::Internal, "Unable to reply with Zenoh")
+            })?;
 
+        self.metrics.on_sent(zenoh_key, buf_len);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = buf_len, "Reply sent");
         Ok(())
     }
-}
 
-#[async_trait]
-impl RpcClient for ULinkZenoh {
-    async fn invoke_method(
+    /// Invoke the method like `invoke_method`, but get the whole response `UMessage`
+    ///
+    /// The `UAttributes` of the response tell its `commstatus`, when the method failed.
+    /// The response is waited for during the TTL of the request, or 1s without TTL.
+    ///
+    /// # Errors
+    /// Will return `Err` with `InvalidArgument` if the request is invalid, with
+    /// `PermissionDenied` if the access control doesn't allow to invoke the method, with
+    /// `DeadlineExceeded` if no response is received in time, or if the response is rejected
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                uuri = ?topic,
+                zenoh_key = tracing::field::Empty,
+                id = tracing::field::Empty,
+                reqid = tracing::field::Empty,
+                trace_id = tracing::field::Empty,
+                parent_id = tracing::field::Empty,
+            )
+        )
+    )]
+    pub async fn invoke_method_message(
         &self,
         topic: UUri,
         payload: UPayload,
         attributes: UAttributes,
-    ) -> RpcClientResult {
+    ) -> Result<UMessage, UStatus> {
         // Validate UUri
         UriValidator::validate(&topic)
-            .map_err(|_| RpcMapperError::UnexpectedError(String::from("Wrong UUri")))?;
+            .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Wrong UUri"))?;
 
         // Validate UAttributes
         {
             // TODO: Check why the validator doesn't have Send
             let validator = Validators::Request.validator();
             if let Err(e) = validator.validate(&attributes) {
-                return Err(RpcMapperError::UnexpectedError(format!(
-                    "Wrong UAttributes {e:?}",
-                )));
+                return Err(UStatus::fail_with_code(
+                    UCode::InvalidArgument,
+                    &format!("Wrong UAttributes {e:?}"),
+                ));
             }
         }
+        self.check_access(Action::Invoke, &topic)?;
 
         // Get Zenoh key
-        let Ok(zenoh_key) = ULinkZenoh::to_zenoh_key_string(&topic) else {
-            return Err(RpcMapperError::UnexpectedError(String::from(
-                "Unable to transform to Zenoh key",
-            )));
-        };
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
+        #[cfg(feature = "tracing")]
+        {
+            let span = tracing::Span::current();
+            span.record("zenoh_key", zenoh_key.as_str());
+            trace::record(&span, &attributes, trace::context(&attributes));
+        }
+
+        let (payload, attributes) =
+            crypto::seal(self.keys.as_deref(), &topic, payload, attributes)?;
+        // Serialized UAttributes into protobuf
+        let attr = codec::encode_attributes(&attributes);
+        let signature = signing::sign(self.signing_keys.as_deref(), &topic, &payload, &attr, true)?;
 
         // Get the data from UPayload
         let Some(Data::Value(buf)) = payload.data else {
             // TODO: Assume we only have Value here, no reference for shared memory
-            return Err(RpcMapperError::InvalidPayload(String::from(
+            return Err(UStatus::fail_with_code(
+                UCode::InvalidArgument,
                 "Wrong UPayload",
-            )));
+            ));
         };
+        let buf_len = buf.len();
 
-        // Serialized UAttributes into protobuf
-        let mut attr = vec![];
-        let Ok(()) = attributes.encode(&mut attr) else {
-            return Err(RpcMapperError::ProtobufError(String::from(
-                "Unable to encode UAttributes",
-            )));
-        };
+        // The response is waited for as long as the request lives
+        let timeout = ULinkZenoh::rpc_timeout(&attributes);
 
         // Add attachment and payload
-        let mut attachment = AttachmentBuilder::new();
-        attachment.insert("uattributes", attr.as_slice());
-        let value = Value::new(buf.into()).encoding(Encoding::WithSuffix(
-            KnownEncoding::AppCustom,
-            payload.format.to_string().into(),
-        ));
+        let mut attachment = codec::attachment(&attr);
+        if let Some(signature) = &signature {
+            signature.attach(&mut attachment);
+        }
+        #[cfg(feature = "tracing")]
+        trace::inject(&mut attachment, &attributes);
+        let value = Value::new(buf.into()).encoding(codec::encoding(payload.format));
         // TODO: Query should support .encoding
-        // TODO: Adjust the timeout
         let getbuilder = self
             .session
             .get(&zenoh_key)
             .with_value(value)
             .with_attachment(attachment.build())
             .target(QueryTarget::BestMatching)
-            .timeout(Duration::from_millis(1000));
+            .timeout(timeout);
 
         // Send the query
+        let start = Instant::now();
         let Ok(replies) = getbuilder.res().await else {
-            return Err(RpcMapperError::UnexpectedError(String::from(
+            #[cfg(feature = "tracing")]
+            tracing::error!("Error while sending Zenoh query");
+            return Err(UStatus::fail_with_code(
+                UCode::Internal,
                 "Error while sending Zenoh query",
-            )));
+            ));
         };
+        self.metrics.on_sent(&zenoh_key, buf_len);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(bytes = buf_len, "Query sent");
 
+        // The reply channel is closed without any reply when the query times out
         let Ok(reply) = replies.recv_async().await else {
-            return Err(RpcMapperError::UnexpectedError(String::from(
-                "Error while receiving Zenoh reply",
-            )));
+            self.metrics.on_rpc_timeout();
+            #[cfg(feature = "tracing")]
+            tracing::warn!("Error while receiving Zenoh reply");
+            return Err(UStatus::fail_with_code(
+                UCode::DeadlineExceeded,
+                "No response received in time",
+            ));
         };
+        self.metrics.on_rpc_latency(start.elapsed());
         match reply.sample {
             Ok(sample) => {
-                let Ok(encoding) = sample.value.encoding.suffix().parse::<i32>() else {
-                    return Err(RpcMapperError::UnexpectedError(String::from(
+                let Ok(encoding) = codec::decode_format(&sample.value.encoding) else {
+                    self.metrics.on_decode_failure();
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("Error while parsing Zenoh encoding");
+                    return Err(UStatus::fail_with_code(
+                        UCode::Internal,
                         "Error while parsing Zenoh encoding",
-                    )));
+                    ));
                 };
-                Ok(UPayload {
-                    length: Some(0),
-                    format: encoding,
-                    data: Some(Data::Value(sample.payload.contiguous().to_vec())),
-                })
+                self.metrics.on_received(&zenoh_key, sample.payload.len());
+                #[cfg(feature = "tracing")]
+                tracing::debug!(bytes = sample.payload.len(), "Reply received");
+                let msg = UMessage {
+                    // Repliers which aren't uProtocol entities don't attach any UAttributes
+                    attributes: codec::attachment_attributes(sample.attachment()).ok(),
+                    source: Some(topic),
+                    payload: Some(UPayload {
+                        length: Some(0),
+                        format: encoding,
+                        data: Some(Data::Value(sample.payload.contiguous().to_vec())),
+                    }),
+                };
+                signing::verify(self.trust_store.as_deref(), &msg, sample.attachment())
+                    .and_then(|()| crypto::open(self.keys.as_deref(), msg))
+            }
+            Err(_) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("Error while parsing Zenoh reply");
+                Err(UStatus::fail_with_code(
+                    UCode::Internal,
+                    "Error while parsing Zenoh reply",
+                ))
             }
-            Err(_) => Err(RpcMapperError::UnexpectedError(String::from(
-                "Error while parsing Zenoh reply",
-            ))),
         }
     }
 }
 
+#[async_trait]
+impl RpcClient for ULinkZenoh {
+    /// Same as [`ULinkZenoh::invoke_method_message`], returning only the response payload
+    ///
+    /// # Errors
+    /// `RpcMapperError` has no code, so the `UStatus` of a failure, e.g. `PermissionDenied` or
+    /// `DeadlineExceeded`, is only given in the text of an `UnexpectedError`. Use
+    /// `invoke_method_message` to get the `UStatus` itself.
+    async fn invoke_method(
+        &self,
+        topic: UUri,
+        payload: UPayload,
+        attributes: UAttributes,
+    ) -> RpcClientResult {
+        let msg = self
+            .invoke_method_message(topic, payload, attributes)
+            .await
+            .map_err(|e| RpcMapperError::UnexpectedError(format!("{e:?}")))?;
+        msg.payload
+            .ok_or_else(|| RpcMapperError::InvalidPayload(String::from("Missing UPayload")))
+    }
+}
+
 #[async_trait]
 impl RpcServer for ULinkZenoh {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uuri = ?method, zenoh_key = tracing::field::Empty))
+    )]
     async fn register_rpc_listener(
         &self,
         method: UUri,
@@ -321,8 +1312,12 @@ impl RpcServer for ULinkZenoh {
         UriValidator::validate(&method)
             .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
 
+        self.check_access(Action::Serve, &method)?;
+
         // Get Zenoh key
         let zenoh_key = ULinkZenoh::to_zenoh_key_string(&method)?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("zenoh_key", zenoh_key.as_str());
         // Generate listener string for users to delete
         let hashmap_key = format!(
             "{}_{:X}",
@@ -331,72 +1326,96 @@ impl RpcServer for ULinkZenoh {
         );
 
         let query_map = self.query_map.clone();
+        let metrics = self.metrics.clone();
+        let metrics_key = zenoh_key.clone();
+        let keys = self.keys.clone();
+        let signing_keys = self.signing_keys.clone();
+        let trust_store = self.trust_store.clone();
         // Setup callback
         let callback = move |query: Query| {
-            // Create UAttribute
-            let Some(attachment) = query.attachment() else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to get attachment",
-                )));
-                return;
-            };
-            let Some(attribute) = attachment.get(&"uattributes".as_bytes()) else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to get uattributes",
-                )));
-                return;
-            };
-            let u_attribute: UAttributes = if let Ok(attr) = Message::decode(&*attribute) {
-                attr
-            } else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to decode attribute",
-                )));
-                return;
-            };
-            // Create UPayload
-            let u_payload = match query.value() {
-                Some(value) => {
-                    let Ok(encoding) = value.encoding.suffix().parse::<i32>() else {
-                        listener(Err(UStatus::fail_with_code(
-                            UCode::Internal,
-                            "Unable to get payload encoding",
-                        )));
-                        return;
-                    };
-                    UPayload {
-                        length: Some(0),
-                        format: encoding,
-                        data: Some(Data::Value(value.payload.contiguous().to_vec())),
-                    }
+            #[cfg(feature = "tracing")]
+            let span = tracing::debug_span!(
+                "query",
+                uuri = ?method,
+                zenoh_key = %query.key_expr(),
+                id = tracing::field::Empty,
+                reqid = tracing::field::Empty,
+                trace_id = tracing::field::Empty,
+                parent_id = tracing::field::Empty,
+            )
+            .entered();
+            // Create UMessage
+            let msg = match ULinkZenoh::query_to_umessage(&method, &query).and_then(|msg| {
+                signing::verify(trust_store.as_deref(), &msg, query.attachment())?;
+                crypto::open(keys.as_deref(), msg)
+            }) {
+                Ok(msg) => msg,
+                Err(e) => {
+                    metrics.on_decode_failure();
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = ?e, "Unable to decode query");
+                    listener(Err(e));
+                    return;
                 }
-                None => UPayload {
-                    length: Some(0),
-                    format: UPayloadFormat::UpayloadFormatUnspecified as i32,
-                    data: None,
-                },
             };
-            // Create UMessage
-            let msg = UMessage {
-                source: Some(method.clone()),
-                attributes: Some(u_attribute.clone()),
-                payload: Some(u_payload),
+            // The listener continues the trace of the request
+            #[cfg(feature = "tracing")]
+            let _context = {
+                let ctx = trace::extract(query.attachment());
+                if let Some(u_attribute) = &msg.attributes {
+                    trace::record(&span, u_attribute, ctx);
+                }
+                ctx.map(trace::TraceContext::enter)
             };
-            if let Some(reqid) = u_attribute.reqid {
+            // Drop the request if its TTL is already expired, telling the caller
+            if let Some(attributes) = msg
+                .attributes
+                .as_ref()
+                .filter(|attributes| ULinkZenoh::is_expired(attributes))
+            {
+                metrics.on_ttl_drop();
+                #[cfg(feature = "tracing")]
+                tracing::debug!("Query dropped for expired TTL");
+                let status = UStatus::fail_with_code(
+                    UCode::DeadlineExceeded,
+                    "The request expired before being received",
+                );
+                if let Ok((sample, attachment)) = ULinkZenoh::status_response(
+                    &method,
+                    attributes.clone(),
+                    &status,
+                    keys.as_deref(),
+                    signing_keys.as_deref(),
+                ) {
+                    rt::spawn(async move {
+                        if let Ok(reply) =
+                            query.reply(Ok(sample)).with_attachment(attachment.build())
+                        {
+                            let _ = reply.res().await;
+                        }
+                    });
+                }
+                listener(Err(status));
+                return;
+            }
+            let payload_len = query.value().map_or(0, |value| value.payload.len());
+            if let Some(reqid) = msg.attributes.as_ref().and_then(|attr| attr.reqid.as_ref()) {
                 query_map
                     .lock()
                     .unwrap()
-                    .insert(ULinkZenoh::uuid_to_string(&reqid), query);
+                    .insert(ULinkZenoh::uuid_to_string(reqid), query);
             } else {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("The request is without reqid in UAttributes");
                 listener(Err(UStatus::fail_with_code(
                     UCode::Internal,
                     "The request is without reqid in UAttributes",
                 )));
                 return;
             }
+            metrics.on_received(&metrics_key, payload_len);
+            #[cfg(feature = "tracing")]
+            tracing::debug!(bytes = payload_len, "Query received");
             listener(Ok(msg));
         };
         if let Ok(queryable) = self
@@ -417,8 +1436,28 @@ impl RpcServer for ULinkZenoh {
             ));
         }
 
+        // Tell the clients the method is served, or don't serve it
+        let token = match self
+            .declare_token(&liveliness::resource_key(AliveKind::Method, &zenoh_key))
+            .await
+        {
+            Ok(token) => token,
+            Err(e) => {
+                self.queryable_map.lock().unwrap().remove(&hashmap_key);
+                return Err(e);
+            }
+        };
+        self.token_map
+            .lock()
+            .unwrap()
+            .insert(hashmap_key.clone(), token);
+
         Ok(hashmap_key)
     }
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uuri = ?method))
+    )]
     async fn unregister_rpc_listener(&self, method: UUri, listener: &str) -> Result<(), UStatus> {
         // Do the validation
         UriValidator::validate(&method)
@@ -437,6 +1476,7 @@ impl RpcServer for ULinkZenoh {
                 "Listener doesn't exist",
             ));
         }
+        self.token_map.lock().unwrap().remove(listener);
 
         Ok(())
     }
@@ -452,6 +1492,20 @@ impl UTransport for ULinkZenoh {
         ))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(
+                uuri = ?topic,
+                id = tracing::field::Empty,
+                reqid = tracing::field::Empty,
+                trace_id = tracing::field::Empty,
+                parent_id = tracing::field::Empty,
+            )
+        )
+    )]
     async fn send(
         &self,
         topic: UUri,
@@ -462,11 +1516,9 @@ impl UTransport for ULinkZenoh {
         UriValidator::validate(&topic)
             .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
 
-        // Get Zenoh key
-        let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
-
-        // Check the type of UAttributes (Publish / Request / Response)
-        match UMessageType::try_from(attributes.r#type) {
+        // Check the type of UAttributes (Publish / Response) and the access before any
+        // encryption or signing
+        let response = match UMessageType::try_from(attributes.r#type) {
             Ok(UMessageType::UmessageTypePublish) => {
                 Validators::Publish
                     .validator()
@@ -477,7 +1529,8 @@ impl UTransport for ULinkZenoh {
                             "Wrong Response UAttributes",
                         )
                     })?;
-                self.send_publish(&zenoh_key, payload, attributes).await
+                self.check_access(Action::Publish, &topic)?;
+                false
             }
             Ok(UMessageType::UmessageTypeResponse) => {
                 Validators::Response
@@ -489,15 +1542,49 @@ impl UTransport for ULinkZenoh {
                             "Wrong Response UAttributes",
                         )
                     })?;
-                self.send_response(&zenoh_key, payload, attributes).await
+                self.check_access(Action::Serve, &topic)?;
+                true
             }
-            _ => Err(UStatus::fail_with_code(
-                UCode::InvalidArgument,
-                "Wrong Message type in UAttributes",
-            )),
+            _ => {
+                return Err(UStatus::fail_with_code(
+                    UCode::InvalidArgument,
+                    "Wrong Message type in UAttributes",
+                ))
+            }
+        };
+
+        // Get Zenoh key
+        let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
+        let (payload, attributes) =
+            crypto::seal(self.keys.as_deref(), &topic, payload, attributes)?;
+        let signature = signing::sign(
+            self.signing_keys.as_deref(),
+            &topic,
+            &payload,
+            &codec::encode_attributes(&attributes),
+            false,
+        )?
+        .map(|signature| signature.signature);
+        #[cfg(feature = "tracing")]
+        trace::record(
+            &tracing::Span::current(),
+            &attributes,
+            trace::context(&attributes),
+        );
+
+        if response {
+            self.send_response(&zenoh_key, payload, attributes, signature)
+                .await
+        } else {
+            self.send_publish(&zenoh_key, payload, attributes, signature)
+                .await
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(uuri = ?topic, zenoh_key = tracing::field::Empty))
+    )]
     async fn register_listener(
         &self,
         topic: UUri,
@@ -507,96 +1594,37 @@ impl UTransport for ULinkZenoh {
         UriValidator::validate(&topic)
             .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
 
+        self.check_access(Action::Subscribe, &topic)?;
+
         // Get Zenoh key
         let zenoh_key = ULinkZenoh::to_zenoh_key_string(&topic)?;
-        // Generate listener string for users to delete
-        let hashmap_key = format!(
-            "{}_{:X}",
-            zenoh_key,
-            self.callback_counter.fetch_add(1, Ordering::SeqCst)
-        );
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("zenoh_key", zenoh_key.as_str());
 
         // Setup callback
-        let callback = move |sample: Sample| {
-            // Create UAttribute
-            let Some(attachment) = sample.attachment() else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to get attachment",
-                )));
-                return;
-            };
-            let Some(attribute) = attachment.get(&"uattributes".as_bytes()) else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to get uattributes",
-                )));
-                return;
-            };
-            let Ok(u_attribute) = Message::decode(&*attribute) else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to decode attribute",
-                )));
-                return;
-            };
-            // Create UPayload
-            let Ok(encoding) = sample.encoding.suffix().parse::<i32>() else {
-                listener(Err(UStatus::fail_with_code(
-                    UCode::Internal,
-                    "Unable to get payload encoding",
-                )));
-                return;
-            };
-            let u_payload = UPayload {
-                length: Some(0),
-                format: encoding,
-                data: Some(Data::Value(sample.payload.contiguous().to_vec())),
-            };
-            // Create UMessage
-            let msg = UMessage {
-                source: Some(topic.clone()),
-                attributes: Some(u_attribute),
-                payload: Some(u_payload),
-            };
-            listener(Ok(msg));
-        };
-        if let Ok(subscriber) = self
-            .session
-            .declare_subscriber(&zenoh_key)
-            .callback_mut(callback)
-            .res()
+        let handler = ULinkZenoh::sample_handler(
+            Some(topic),
+            listener,
+            self.metrics.clone(),
+            self.keys.clone(),
+            self.trust_store.clone(),
+            self.foreign_publishers,
+        );
+        self.declare_listener(&zenoh_key, true, move |sample: Sample| handler(&sample))
             .await
-        {
-            self.subscriber_map
-                .lock()
-                .unwrap()
-                .insert(hashmap_key.clone(), subscriber);
-        } else {
-            return Err(UStatus::fail_with_code(
-                UCode::Internal,
-                "Unable to register callback with Zenoh",
-            ));
-        }
-
-        Ok(hashmap_key)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self), fields(uuri = ?topic))
+    )]
     async fn unregister_listener(&self, topic: UUri, listener: &str) -> Result<(), UStatus> {
         // Do the validation
         UriValidator::validate(&topic)
             .map_err(|_| UStatus::fail_with_code(UCode::InvalidArgument, "Invalid topic"))?;
         // TODO: Check whether we still need topic or not (Compare topic with listener?)
 
-        if !self.subscriber_map.lock().unwrap().contains_key(listener) {
-            return Err(UStatus::fail_with_code(
-                UCode::InvalidArgument,
-                "Listener doesn't exist",
-            ));
-        }
-
-        self.subscriber_map.lock().unwrap().remove(listener);
-        Ok(())
+        self.unregister_pattern_listener(listener)
     }
 }
 
@@ -0,0 +1,235 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Zenoh liveliness tokens declared for the uEntities, RPC methods and topics of a `ULinkZenoh`.
+//!
+//! The liveliness keys are:
+//! * `up/alive/entity/<id><version_major>/<name>` for a uEntity (hex id on 4 digits, hex version on 2)
+//! * `up/alive/method/<zenoh key>` for an RPC method served with `register_rpc_listener`
+//! * `up/alive/topic/<zenoh key>` for a topic listened with `register_listener`
+use crate::ULinkZenoh;
+use uprotocol_sdk::uprotocol::{UCode, UEntity, UStatus, UUri};
+use zenoh::{
+    prelude::{Sample, SampleKind},
+    subscriber::Subscriber,
+};
+
+pub(crate) const LIVELINESS_PREFIX: &str = "up/alive";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AliveKind {
+    Entity,
+    Method,
+    Topic,
+}
+
+impl AliveKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            AliveKind::Entity => "entity",
+            AliveKind::Method => "method",
+            AliveKind::Topic => "topic",
+        }
+    }
+}
+
+/// A uEntity, RPC method or topic currently alive.
+/// For an entity, only the `entity` of the `UUri` is filled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AliveResource {
+    pub kind: AliveKind,
+    pub uri: UUri,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum LivelinessEvent {
+    Appeared(AliveResource),
+    Disappeared(AliveResource),
+}
+
+/// Liveliness key expression matching all the resources of the kind
+pub(crate) fn kind_key_expr(kind: AliveKind) -> String {
+    format!("{LIVELINESS_PREFIX}/{}/**", kind.as_str())
+}
+
+/// Liveliness key of a method or a topic
+pub(crate) fn resource_key(kind: AliveKind, zenoh_key: &str) -> String {
+    format!("{LIVELINESS_PREFIX}/{}/{zenoh_key}", kind.as_str())
+}
+
+/// Liveliness key of a uEntity
+pub(crate) fn entity_key(entity: &UEntity) -> Result<String, UStatus> {
+    let (Some(id), Some(version_major)) = (entity.id, entity.version_major) else {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The uEntity needs an id and a major version",
+        ));
+    };
+    if id > u32::from(u16::MAX) || version_major > u32::from(u8::MAX) {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The uEntity id or major version is out of range",
+        ));
+    }
+    // The name becomes one chunk of the key expression
+    if entity.name.is_empty()
+        || entity
+            .name
+            .contains(|c| matches!(c, '/' | '*' | '$' | '?' | '#'))
+    {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The uEntity name can't be used in a Zenoh key",
+        ));
+    }
+    Ok(format!(
+        "{LIVELINESS_PREFIX}/entity/{id:04x}{version_major:02x}/{}",
+        entity.name
+    ))
+}
+
+/// Get back the resource from its liveliness key
+pub(crate) fn parse_key(key: &str) -> Option<AliveResource> {
+    let rest = key.strip_prefix(LIVELINESS_PREFIX)?.strip_prefix('/')?;
+    let (kind, rest) = rest.split_once('/')?;
+    match kind {
+        "entity" => {
+            let (ids, name) = rest.split_once('/')?;
+            if ids.len() != 6 || name.is_empty() {
+                return None;
+            }
+            let id = u32::from_str_radix(&ids[..4], 16).ok()?;
+            let version_major = u32::from_str_radix(&ids[4..], 16).ok()?;
+            Some(AliveResource {
+                kind: AliveKind::Entity,
+                uri: UUri {
+                    entity: Some(UEntity {
+                        name: name.to_string(),
+                        id: Some(id),
+                        version_major: Some(version_major),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            })
+        }
+        "method" | "topic" => Some(AliveResource {
+            kind: if kind == "method" {
+                AliveKind::Method
+            } else {
+                AliveKind::Topic
+            },
+            uri: ULinkZenoh::from_zenoh_key_string(rest).ok()?,
+        }),
+        _ => None,
+    }
+}
+
+/// Stream of the appear/disappear events of the uProtocol resources.
+/// Stops watching when dropped.
+pub struct LivelinessWatcher {
+    pub(crate) _subscriber: Subscriber<'static, ()>,
+    pub(crate) receiver: flume::Receiver<LivelinessEvent>,
+}
+
+impl LivelinessWatcher {
+    pub(crate) fn callback(
+        sender: flume::Sender<LivelinessEvent>,
+    ) -> impl Fn(Sample) + Send + Sync + 'static {
+        move |sample: Sample| {
+            let Some(resource) = parse_key(sample.key_expr.as_str()) else {
+                return;
+            };
+            let event = match sample.kind {
+                SampleKind::Put => LivelinessEvent::Appeared(resource),
+                SampleKind::Delete => LivelinessEvent::Disappeared(resource),
+            };
+            let _ = sender.send(event);
+        }
+    }
+
+    /// Wait for the next event, `None` if the session is closed
+    pub async fn recv_async(&self) -> Option<LivelinessEvent> {
+        self.receiver.recv_async().await.ok()
+    }
+
+    /// Get the next event if there is one already received
+    #[must_use]
+    pub fn try_recv(&self) -> Option<LivelinessEvent> {
+        self.receiver.try_recv().ok()
+    }
+
+    /// Get the events as a `Stream`
+    #[must_use]
+    pub fn stream(&self) -> flume::r#async::RecvStream<'_, LivelinessEvent> {
+        self.receiver.stream()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entity_key() {
+        let entity = UEntity {
+            name: "body.access".to_string(),
+            version_major: Some(1),
+            id: Some(1234),
+            ..Default::default()
+        };
+        let key = entity_key(&entity).unwrap();
+        assert_eq!(key, "up/alive/entity/04d201/body.access");
+        assert_eq!(
+            parse_key(&key),
+            Some(AliveResource {
+                kind: AliveKind::Entity,
+                uri: UUri {
+                    entity: Some(entity),
+                    ..Default::default()
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_entity_key_invalid() {
+        let entity = UEntity {
+            name: "body/access".to_string(),
+            version_major: Some(1),
+            id: Some(1234),
+            ..Default::default()
+        };
+        assert!(entity_key(&entity).is_err());
+        let entity = UEntity {
+            name: "body.access".to_string(),
+            id: Some(1234),
+            ..Default::default()
+        };
+        assert!(entity_key(&entity).is_err());
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!(
+            resource_key(AliveKind::Topic, "0100162e04d20100"),
+            "up/alive/topic/0100162e04d20100"
+        );
+        let resource = parse_key("up/alive/topic/0100162e04d20100").unwrap();
+        assert_eq!(resource.kind, AliveKind::Topic);
+        assert_eq!(resource.uri.entity.unwrap().id, Some(1234));
+        assert_eq!(resource.uri.resource.unwrap().id, Some(5678));
+        assert_eq!(parse_key("up/alive/unknown/0100162e04d20100"), None);
+        assert_eq!(parse_key("other/alive/topic/0100162e04d20100"), None);
+    }
+}
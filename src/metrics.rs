@@ -0,0 +1,323 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use std::collections::HashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Mutex,
+};
+use std::time::Duration;
+
+/// Upper bounds (in milliseconds) of the RPC round-trip time buckets
+pub const RPC_LATENCY_BOUNDS_MS: [u64; 10] = [1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500];
+
+/// Traffic counters of one topic, keyed by its Zenoh key
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopicMetrics {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+}
+
+/// Histogram of the RPC round-trip time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Number of observations per bucket of `RPC_LATENCY_BOUNDS_MS`, the last one is `+Inf`
+    pub buckets: Vec<u64>,
+    pub sum_us: u64,
+    pub count: u64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        LatencyHistogram {
+            buckets: vec![0; RPC_LATENCY_BOUNDS_MS.len() + 1],
+            sum_us: 0,
+            count: 0,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub fn observe(&mut self, latency: Duration) {
+        let latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        let index = RPC_LATENCY_BOUNDS_MS
+            .iter()
+            .position(|bound| latency_ms <= *bound)
+            .unwrap_or(RPC_LATENCY_BOUNDS_MS.len());
+        self.buckets[index] += 1;
+        self.sum_us = self
+            .sum_us
+            .saturating_add(u64::try_from(latency.as_micros()).unwrap_or(u64::MAX));
+        self.count += 1;
+    }
+}
+
+/// Metrics collected by `ULinkZenoh`
+#[derive(Default)]
+pub(crate) struct ULinkMetrics {
+    topics: Mutex<HashMap<String, TopicMetrics>>,
+    rpc_latency: Mutex<LatencyHistogram>,
+    rpc_timeouts: AtomicU64,
+    decode_failures: AtomicU64,
+    ttl_drops: AtomicU64,
+    storage_failures: AtomicU64,
+}
+
+impl ULinkMetrics {
+    pub(crate) fn on_sent(&self, zenoh_key: &str, bytes: usize) {
+        let mut topics = self.topics.lock().unwrap();
+        let topic = topics.entry(zenoh_key.to_string()).or_default();
+        topic.messages_sent += 1;
+        topic.bytes_sent += bytes as u64;
+    }
+
+    pub(crate) fn on_received(&self, zenoh_key: &str, bytes: usize) {
+        let mut topics = self.topics.lock().unwrap();
+        let topic = topics.entry(zenoh_key.to_string()).or_default();
+        topic.messages_received += 1;
+        topic.bytes_received += bytes as u64;
+    }
+
+    pub(crate) fn on_rpc_latency(&self, latency: Duration) {
+        self.rpc_latency.lock().unwrap().observe(latency);
+    }
+
+    pub(crate) fn on_rpc_timeout(&self) {
+        self.rpc_timeouts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_decode_failure(&self) {
+        self.decode_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_ttl_drop(&self) {
+        self.ttl_drops.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn on_storage_failure(&self) {
+        self.storage_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn topic(&self, zenoh_key: &str) -> Option<TopicMetrics> {
+        self.topics.lock().unwrap().get(zenoh_key).cloned()
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            topics: self.topics.lock().unwrap().clone(),
+            rpc_latency: self.rpc_latency.lock().unwrap().clone(),
+            rpc_timeouts: self.rpc_timeouts.load(Ordering::Relaxed),
+            decode_failures: self.decode_failures.load(Ordering::Relaxed),
+            ttl_drops: self.ttl_drops.load(Ordering::Relaxed),
+            storage_failures: self.storage_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of the metrics of a `ULinkZenoh`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetricsSnapshot {
+    /// Traffic counters per Zenoh key, the hexadecimal micro form of the topic `UUri`
+    pub topics: HashMap<String, TopicMetrics>,
+    pub rpc_latency: LatencyHistogram,
+    pub rpc_timeouts: u64,
+    pub decode_failures: u64,
+    pub ttl_drops: u64,
+    /// Messages received by the storages of the uLink but unable to be written
+    pub storage_failures: u64,
+}
+
+#[cfg(feature = "prometheus")]
+impl MetricsSnapshot {
+    /// Export the metrics in the Prometheus text exposition format.
+    /// The topic counters are labelled with the `uri` of the topic, in the
+    /// `<entity id>/<major version>/<resource id>` form (the Zenoh key if it can't be decoded).
+    #[must_use]
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let mut keys: Vec<&String> = self.topics.keys().collect();
+        keys.sort();
+        let labels: HashMap<&String, String> = keys
+            .iter()
+            .map(|key| {
+                let uri = crate::ULinkZenoh::from_zenoh_key_string(key)
+                    .map_or_else(|_| (*key).clone(), |uri| crate::uri::format_uri(&uri));
+                (*key, uri)
+            })
+            .collect();
+        let per_topic: [(&str, &str, fn(&TopicMetrics) -> u64); 4] = [
+            ("messages_sent", "Messages sent per topic", |t| {
+                t.messages_sent
+            }),
+            ("bytes_sent", "Payload bytes sent per topic", |t| {
+                t.bytes_sent
+            }),
+            ("messages_received", "Messages received per topic", |t| {
+                t.messages_received
+            }),
+            ("bytes_received", "Payload bytes received per topic", |t| {
+                t.bytes_received
+            }),
+        ];
+        for (name, help, get) in per_topic {
+            let _ = writeln!(out, "# HELP uprotocol_zenoh_{name}_total {help}");
+            let _ = writeln!(out, "# TYPE uprotocol_zenoh_{name}_total counter");
+            for key in &keys {
+                let _ = writeln!(
+                    out,
+                    "uprotocol_zenoh_{name}_total{{uri=\"{}\"}} {}",
+                    labels[*key],
+                    get(&self.topics[*key])
+                );
+            }
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP uprotocol_zenoh_rpc_latency_seconds RPC round-trip time"
+        );
+        let _ = writeln!(out, "# TYPE uprotocol_zenoh_rpc_latency_seconds histogram");
+        let mut cumulative = 0;
+        for (bound, count) in RPC_LATENCY_BOUNDS_MS.iter().zip(&self.rpc_latency.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "uprotocol_zenoh_rpc_latency_seconds_bucket{{le=\"{}.{:03}\"}} {cumulative}",
+                bound / 1000,
+                bound % 1000
+            );
+        }
+        let _ = writeln!(
+            out,
+            "uprotocol_zenoh_rpc_latency_seconds_bucket{{le=\"+Inf\"}} {}",
+            self.rpc_latency.count
+        );
+        let _ = writeln!(
+            out,
+            "uprotocol_zenoh_rpc_latency_seconds_sum {}.{:06}",
+            self.rpc_latency.sum_us / 1_000_000,
+            self.rpc_latency.sum_us % 1_000_000
+        );
+        let _ = writeln!(
+            out,
+            "uprotocol_zenoh_rpc_latency_seconds_count {}",
+            self.rpc_latency.count
+        );
+
+        let totals = [
+            (
+                "rpc_timeouts",
+                "RPC invocations without reply",
+                self.rpc_timeouts,
+            ),
+            (
+                "decode_failures",
+                "Received messages unable to be decoded",
+                self.decode_failures,
+            ),
+            (
+                "ttl_drops",
+                "Received messages dropped for expired TTL",
+                self.ttl_drops,
+            ),
+            (
+                "storage_failures",
+                "Received messages unable to be stored",
+                self.storage_failures,
+            ),
+        ];
+        for (name, help, value) in totals {
+            let _ = writeln!(out, "# HELP uprotocol_zenoh_{name}_total {help}");
+            let _ = writeln!(out, "# TYPE uprotocol_zenoh_{name}_total counter");
+            let _ = writeln!(out, "uprotocol_zenoh_{name}_total {value}");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        histogram.observe(Duration::from_micros(500));
+        histogram.observe(Duration::from_millis(30));
+        histogram.observe(Duration::from_secs(10));
+        assert_eq!(histogram.buckets, vec![1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(histogram.sum_us, 10_030_500);
+        assert_eq!(histogram.count, 3);
+    }
+
+    #[test]
+    fn test_topic_metrics() {
+        let metrics = ULinkMetrics::default();
+        metrics.on_sent("0100162e04d20100", 12);
+        metrics.on_sent("0100162e04d20100", 8);
+        metrics.on_received("0100162e04d20100", 8);
+        metrics.on_decode_failure();
+        assert_eq!(
+            metrics.topic("0100162e04d20100"),
+            Some(TopicMetrics {
+                messages_sent: 2,
+                bytes_sent: 20,
+                messages_received: 1,
+                bytes_received: 8,
+            })
+        );
+        assert_eq!(metrics.topic("0100162e04d20101"), None);
+        assert_eq!(metrics.snapshot().decode_failures, 1);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_to_prometheus() {
+        let metrics = ULinkMetrics::default();
+        metrics.on_sent("0100162e04d20100", 12);
+        metrics.on_received("0100162e04d20100", 5);
+        metrics.on_received("0100162e04d20100", 3);
+        metrics.on_received("not_a_topic", 1);
+        metrics.on_rpc_latency(Duration::from_millis(7));
+        metrics.on_rpc_timeout();
+        metrics.on_decode_failure();
+        metrics.on_decode_failure();
+        metrics.on_storage_failure();
+        let text = metrics.snapshot().to_prometheus();
+
+        // The topics are labelled with their UUri
+        assert!(text.contains("# TYPE uprotocol_zenoh_messages_sent_total counter"));
+        assert!(text.contains("uprotocol_zenoh_messages_sent_total{uri=\"1234/1/5678\"} 1"));
+        assert!(text.contains("uprotocol_zenoh_bytes_sent_total{uri=\"1234/1/5678\"} 12"));
+        assert!(text.contains("uprotocol_zenoh_messages_received_total{uri=\"1234/1/5678\"} 2"));
+        assert!(text.contains("uprotocol_zenoh_bytes_received_total{uri=\"1234/1/5678\"} 8"));
+        assert!(text.contains("uprotocol_zenoh_messages_received_total{uri=\"not_a_topic\"} 1"));
+        assert!(!text.contains("zenoh_key"));
+
+        assert!(text.contains("# TYPE uprotocol_zenoh_rpc_latency_seconds histogram"));
+        assert!(text.contains("uprotocol_zenoh_rpc_latency_seconds_bucket{le=\"0.005\"} 0"));
+        assert!(text.contains("uprotocol_zenoh_rpc_latency_seconds_bucket{le=\"0.010\"} 1"));
+        assert!(text.contains("uprotocol_zenoh_rpc_latency_seconds_bucket{le=\"+Inf\"} 1"));
+        assert!(text.contains("uprotocol_zenoh_rpc_latency_seconds_sum 0.007000"));
+        assert!(text.contains("uprotocol_zenoh_rpc_latency_seconds_count 1"));
+
+        assert!(text.contains("uprotocol_zenoh_rpc_timeouts_total 1"));
+        assert!(text.contains("uprotocol_zenoh_decode_failures_total 2"));
+        assert!(text.contains("uprotocol_zenoh_ttl_drops_total 0"));
+        assert!(text.contains("uprotocol_zenoh_storage_failures_total 1"));
+    }
+}
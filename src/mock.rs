@@ -0,0 +1,231 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Mock RPC services, answering with the canned responses of a declarative file.
+//!
+//! The file is JSON, each method giving a response payload or an error, and an optional delay:
+//! ```json
+//! {
+//!   "methods": [
+//!     { "method": "test.service@1234/1/1", "response": { "text": "pong" }, "delay_ms": 100 },
+//!     { "method": "test.service@1234/1/2", "response": { "json": { "open": true } } },
+//!     { "method": "test.service@1234/1/3", "error": { "code": "unavailable", "message": "Down" } }
+//!   ]
+//! }
+//! ```
+//! The methods are `UUri`s in the forms of [`crate::cli`], with the entity name that serving needs.
+//! The payload is one of `text`, `hex` or `json`, the error code the name of a `UCode`.
+use crate::{cli, handler, rt, ULinkZenoh};
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use uprotocol_sdk::{
+    rpc::RpcServer,
+    uprotocol::{Data, UCode, UPayload, UPayloadFormat, UStatus, UUri},
+};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MockFile {
+    methods: Vec<MockEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MockEntry {
+    method: String,
+    #[serde(default)]
+    delay_ms: u64,
+    response: Option<MockPayload>,
+    error: Option<MockError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MockPayload {
+    Text(String),
+    Hex(String),
+    Json(serde_json::Value),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MockError {
+    code: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Canned response of a method
+#[derive(Clone, Debug)]
+pub struct MockMethod {
+    pub method: UUri,
+    /// Time waited before responding
+    pub delay: Duration,
+    /// Response payload, or the error sent back as `commstatus`
+    pub response: Result<UPayload, UStatus>,
+}
+
+impl MockMethod {
+    fn from_entry(entry: MockEntry) -> Result<MockMethod, UStatus> {
+        let invalid = |message: String| UStatus::fail_with_code(UCode::InvalidArgument, &message);
+        let method = cli::parse_uri(&entry.method).map_err(invalid)?;
+        let response = match (entry.response, entry.error) {
+            (Some(payload), None) => {
+                let (format, data) = match payload {
+                    MockPayload::Text(text) => {
+                        (UPayloadFormat::UpayloadFormatText, text.into_bytes())
+                    }
+                    MockPayload::Hex(hex) => (
+                        UPayloadFormat::UpayloadFormatRaw,
+                        cli::parse_hex(&hex).map_err(invalid)?,
+                    ),
+                    MockPayload::Json(json) => (
+                        UPayloadFormat::UpayloadFormatJson,
+                        json.to_string().into_bytes(),
+                    ),
+                };
+                Ok(UPayload {
+                    length: Some(0),
+                    format: format as i32,
+                    data: Some(Data::Value(data)),
+                })
+            }
+            (None, Some(error)) => {
+                let code = UCode::from_str_name(&error.code.to_uppercase())
+                    .filter(|code| *code != UCode::Ok)
+                    .ok_or_else(|| invalid(format!("Invalid error code: {}", error.code)))?;
+                Err(UStatus::fail_with_code(code, &error.message))
+            }
+            _ => {
+                return Err(invalid(format!(
+                    "Either a response or an error is expected for {}",
+                    entry.method
+                )))
+            }
+        };
+        Ok(MockMethod {
+            method,
+            delay: Duration::from_millis(entry.delay_ms),
+            response,
+        })
+    }
+}
+
+/// The methods declared by a mock file
+#[derive(Clone, Debug, Default)]
+pub struct MockConfig {
+    pub methods: Vec<MockMethod>,
+}
+
+impl MockConfig {
+    /// # Errors
+    /// Will return `Err` if the JSON isn't a valid mock declaration
+    pub fn from_json(json: &str) -> Result<MockConfig, UStatus> {
+        let file: MockFile = serde_json::from_str(json)
+            .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e.to_string()))?;
+        let methods = file
+            .methods
+            .into_iter()
+            .map(MockMethod::from_entry)
+            .collect::<Result<_, _>>()?;
+        Ok(MockConfig { methods })
+    }
+
+    /// # Errors
+    /// Will return `Err` if unable to read the file, or if it isn't a valid mock declaration
+    pub fn from_file(path: impl AsRef<Path>) -> Result<MockConfig, UStatus> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| UStatus::fail_with_code(UCode::InvalidArgument, &e.to_string()))?;
+        MockConfig::from_json(&json)
+    }
+}
+
+/// Serve the mocked methods until stopped
+pub struct MockServer {
+    ulink: Arc<ULinkZenoh>,
+    listeners: Vec<(UUri, String)>,
+}
+
+impl MockServer {
+    /// # Errors
+    /// Will return `Err` if unable to register the RPC listeners
+    pub async fn start(ulink: &Arc<ULinkZenoh>, config: MockConfig) -> Result<MockServer, UStatus> {
+        let mut listeners = vec![];
+        for MockMethod {
+            method,
+            delay,
+            response,
+        } in config.methods
+        {
+            let listener = handler::register_rpc_handler(ulink, method.clone(), move |_, _| {
+                let response = response.clone();
+                async move {
+                    if !delay.is_zero() {
+                        rt::sleep(delay).await;
+                    }
+                    response
+                }
+            })
+            .await?;
+            listeners.push((method, listener));
+        }
+        Ok(MockServer {
+            ulink: ulink.clone(),
+            listeners,
+        })
+    }
+
+    /// # Errors
+    /// Will return `Err` if unable to unregister the RPC listeners
+    pub async fn stop(self) -> Result<(), UStatus> {
+        for (method, listener) in self.listeners {
+            self.ulink
+                .unregister_rpc_listener(method, &listener)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_config() {
+        let config = MockConfig::from_json(
+            r#"{"methods": [
+                {"method": "test.service@1234/1/1", "response": {"hex": "0102"}, "delay_ms": 10},
+                {"method": "test.service@1234/1/2", "error": {"code": "unavailable"}}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(config.methods.len(), 2);
+        assert_eq!(config.methods[0].delay, Duration::from_millis(10));
+        let payload = config.methods[0].response.clone().unwrap();
+        assert_eq!(payload.data, Some(Data::Value(vec![1, 2])));
+        let status = config.methods[1].response.clone().unwrap_err();
+        assert_eq!(status.code, UCode::Unavailable as i32);
+
+        // Exactly one of response and error, with a known code
+        for json in [
+            r#"{"methods": [{"method": "test.service@1234/1/1"}]}"#,
+            r#"{"methods": [{"method": "test.service@1234/1/1", "error": {"code": "oops"}}]}"#,
+            r#"{"methods": [{"method": "1234/1", "response": {"text": "pong"}}]}"#,
+        ] {
+            assert!(MockConfig::from_json(json).is_err());
+        }
+    }
+}
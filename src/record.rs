@@ -0,0 +1,242 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! On-disk format of the recorded `UMessage`s.
+//!
+//! A record file is a sequence of length-delimited protobuf [`Record`]s, each one holding the
+//! reception time and the whole `UMessage` (source, attributes, payload and format).
+//! The files written by `urecord` start with [`MAGIC`] and a length-delimited [`RecordHeader`],
+//! so they can be identified without knowing where they come from.
+use prost::Message;
+use std::io::{self, Read, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+use uprotocol_sdk::uprotocol::UMessage;
+
+pub const MAGIC: &[u8; 4] = b"UREC";
+pub const RECORD_FORMAT: &str = "uprotocol.v1.UMessage";
+pub const RECORD_VERSION: u32 = 1;
+/// Largest record accepted when reading, so that a corrupted length can't exhaust the memory
+pub const MAX_RECORD_LENGTH: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, PartialEq, Message)]
+pub struct RecordHeader {
+    /// Protobuf type of the recorded messages
+    #[prost(string, tag = "1")]
+    pub format: String,
+    #[prost(uint32, tag = "2")]
+    pub version: u32,
+    /// Creation time, in milliseconds since the Unix epoch
+    #[prost(uint64, tag = "3")]
+    pub created: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Record {
+    /// Reception time, in milliseconds since the Unix epoch
+    #[prost(uint64, tag = "1")]
+    pub timestamp: u64,
+    #[prost(message, optional, tag = "2")]
+    pub message: Option<UMessage>,
+    /// `UAttributes` as serialized by the sender, kept by the storages to check the signature
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub attributes: Option<Vec<u8>>,
+    /// Signature of the message, see [`crate::signing`]
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub signature: Option<Vec<u8>>,
+}
+
+impl Record {
+    /// Record the message with the current time
+    #[must_use]
+    pub fn now(message: UMessage) -> Self {
+        Record {
+            timestamp: now_millis(),
+            message: Some(message),
+            attributes: None,
+            signature: None,
+        }
+    }
+}
+
+/// Current time, in milliseconds since the Unix epoch
+#[must_use]
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+}
+
+/// Start a record file
+///
+/// # Errors
+/// Will return `Err` if unable to write
+pub fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    let header = RecordHeader {
+        format: RECORD_FORMAT.to_string(),
+        version: RECORD_VERSION,
+        created: now_millis(),
+    };
+    writer.write_all(MAGIC)?;
+    writer.write_all(&header.encode_length_delimited_to_vec())
+}
+
+/// Read the start of a record file
+///
+/// # Errors
+/// Will return `Err` if unable to read, or if it isn't a record file of a supported version
+pub fn read_header(reader: &mut impl Read) -> io::Result<RecordHeader> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid("Not a record file"));
+    }
+    let length = read_length(reader)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+    let buf = read_exact_bounded(reader, length)?;
+    let header = RecordHeader::decode(buf.as_slice()).map_err(|_| invalid("Invalid header"))?;
+    if header.format != RECORD_FORMAT || header.version > RECORD_VERSION {
+        return Err(invalid("Unsupported record format"));
+    }
+    Ok(header)
+}
+
+// Read a varint length prefix, `None` at the end of the reader
+fn read_length(reader: &mut impl Read) -> io::Result<Option<usize>> {
+    let mut length: u64 = 0;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        length |= u64::from(byte[0] & 0x7f) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return usize::try_from(length)
+                .map(Some)
+                .map_err(|_| io::ErrorKind::InvalidData.into());
+        }
+    }
+    Err(io::ErrorKind::InvalidData.into())
+}
+
+// Read the given number of bytes, allocating only as much as the reader actually has
+fn read_exact_bounded(reader: &mut impl Read, length: usize) -> io::Result<Vec<u8>> {
+    if length > MAX_RECORD_LENGTH {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Record too long",
+        ));
+    }
+    let mut buf = vec![];
+    reader
+        .take(u64::try_from(length).unwrap_or(u64::MAX))
+        .read_to_end(&mut buf)?;
+    if buf.len() < length {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(buf)
+}
+
+/// Append the record, in one write so that a concurrent reader never sees half of it
+///
+/// # Errors
+/// Will return `Err` if unable to write
+pub fn write_record(writer: &mut impl Write, record: &Record) -> io::Result<usize> {
+    let buf = record.encode_length_delimited_to_vec();
+    writer.write_all(&buf)?;
+    Ok(buf.len())
+}
+
+/// Iterate over the records of a reader, a truncated last record being an error
+pub struct RecordReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> RecordReader<R> {
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        RecordReader { reader }
+    }
+}
+
+impl<R: Read> Iterator for RecordReader<R> {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let length = match read_length(&mut self.reader) {
+            Ok(Some(length)) => length,
+            Ok(None) => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        let buf = match read_exact_bounded(&mut self.reader, length) {
+            Ok(buf) => buf,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(
+            Record::decode(buf.as_slice())
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::uprotocol::{Data, UPayload, UPayloadFormat};
+
+    #[test]
+    fn test_write_and_read_records() {
+        let mut buf = vec![];
+        write_header(&mut buf).unwrap();
+        for (timestamp, data) in [(1, "first"), (2, "second")] {
+            let record = Record {
+                timestamp,
+                message: Some(UMessage {
+                    payload: Some(UPayload {
+                        length: Some(0),
+                        format: UPayloadFormat::UpayloadFormatText as i32,
+                        data: Some(Data::Value(data.as_bytes().to_vec())),
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            write_record(&mut buf, &record).unwrap();
+        }
+        let mut reader = buf.as_slice();
+        assert_eq!(read_header(&mut reader).unwrap().version, RECORD_VERSION);
+        let records: Vec<Record> = RecordReader::new(reader)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].timestamp, 2);
+
+        // A truncated record is an error
+        buf.pop();
+        let mut reader = buf.as_slice();
+        read_header(&mut reader).unwrap();
+        let mut reader = RecordReader::new(reader);
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().unwrap().is_err());
+
+        // So is a length beyond what's left, without allocating it
+        let mut buf = vec![];
+        write_header(&mut buf).unwrap();
+        buf.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+        let mut reader = buf.as_slice();
+        read_header(&mut reader).unwrap();
+        assert!(RecordReader::new(reader).next().unwrap().is_err());
+    }
+}
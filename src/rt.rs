@@ -0,0 +1,91 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Runtime helpers used for the internal spawning and sleeping.
+//!
+//! The runtime is selected with the `async-std` (default) or `tokio` feature.
+//! If both are enabled, Tokio is used.
+use std::future::Future;
+use std::time::Duration;
+
+#[cfg(not(any(feature = "async-std", feature = "tokio")))]
+compile_error!("Either the `async-std` or the `tokio` feature must be enabled");
+
+#[cfg(feature = "tokio")]
+fn runtime() -> tokio::runtime::Handle {
+    use std::sync::OnceLock;
+    static RUNTIME: OnceLock<tokio::runtime::Runtime> = OnceLock::new();
+
+    // Zenoh callbacks aren't run inside a Tokio runtime, so fall back to our own one
+    tokio::runtime::Handle::try_current().unwrap_or_else(|_| {
+        RUNTIME
+            .get_or_init(|| {
+                tokio::runtime::Builder::new_multi_thread()
+                    .enable_all()
+                    .build()
+                    .expect("Unable to create Tokio runtime")
+            })
+            .handle()
+            .clone()
+    })
+}
+
+/// Sleep for the given duration
+pub async fn sleep(duration: Duration) {
+    #[cfg(feature = "tokio")]
+    tokio::time::sleep(duration).await;
+    #[cfg(not(feature = "tokio"))]
+    async_std::task::sleep(duration).await;
+}
+
+/// Run the future in the background
+pub fn spawn<F>(future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    #[cfg(feature = "tokio")]
+    runtime().spawn(future);
+    #[cfg(not(feature = "tokio"))]
+    async_std::task::spawn(future);
+}
+
+/// Wait for the future, returning `None` if it isn't ready before the timeout
+#[cfg(feature = "tokio")]
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(duration, future).await.ok()
+}
+
+/// Wait for the future, returning `None` if it isn't ready before the timeout
+#[cfg(not(feature = "tokio"))]
+pub async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    async_std::future::timeout(duration, future).await.ok()
+}
+
+/// Block the current thread until the future is completed
+///
+/// # Panics
+/// Will panic if called from a Tokio current-thread runtime
+#[cfg(feature = "tokio")]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    if tokio::runtime::Handle::try_current().is_ok() {
+        tokio::task::block_in_place(|| runtime().block_on(future))
+    } else {
+        runtime().block_on(future)
+    }
+}
+
+/// Block the current thread until the future is completed
+#[cfg(not(feature = "tokio"))]
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    async_std::task::block_on(future)
+}
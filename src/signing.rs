@@ -0,0 +1,411 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Ed25519 signing of the messages, so that the listeners only get what the uEntity of the topic
+//! or RPC method really sent.
+//!
+//! A uLink with [`SigningKeys`] signs the messages sent on the topics and RPC methods of the
+//! uEntities it has a key for, i.e. their publications and responses, and the requests with the
+//! key of its requester uEntity. The signature covers the Zenoh key, the serialized `UAttributes`
+//! and the payload, and is carried in the `usignature` attachment item, with the id of the
+//! requester in the `usigner` item for a request. A uLink with a [`TrustStore`] verifies the
+//! messages against the key trusted for the uEntity of their source, or for the requester, and
+//! delivers the rejected ones as `Unauthenticated` errors according to the [`SignaturePolicy`] of
+//! the topic or RPC method.
+//!
+//! A `storage::Storage` keeps the signatures, so that its replies are verified as well.
+use crate::{codec, ULinkZenoh};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use std::collections::HashMap;
+use uprotocol_sdk::uprotocol::{
+    Data, UCode, UEntity, UMessage, UMessageType, UPayload, UStatus, UUri,
+};
+use zenoh::sample::{Attachment, AttachmentBuilder};
+
+/// Attachment item holding the signature
+pub const SIGNATURE_KEY: &str = "usignature";
+/// Attachment item holding the id of the uEntity which signed a request, big-endian
+pub const SIGNER_KEY: &str = "usigner";
+
+/// What to do with the messages of a topic or RPC method
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignaturePolicy {
+    /// Deliver all the messages without checking them
+    Ignore,
+    /// Reject the mis-signed messages, but deliver the unsigned ones
+    AllowUnsigned,
+    /// Reject the unsigned and the mis-signed messages
+    Require,
+}
+
+/// Private keys of the local uEntities, to sign what they send
+#[derive(Default)]
+pub struct SigningKeys {
+    keys: HashMap<u32, SigningKey>,
+    // The uEntity signing the requests
+    requester: Option<u32>,
+}
+
+impl SigningKeys {
+    #[must_use]
+    pub fn new() -> SigningKeys {
+        SigningKeys::default()
+    }
+
+    /// Sign the messages sent on the topics and RPC methods of the uEntity with the key
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity has no id
+    pub fn insert(&mut self, entity: &UEntity, key: SigningKey) -> Result<(), UStatus> {
+        self.keys.insert(entity_id(Some(entity))?, key);
+        Ok(())
+    }
+
+    /// Sign the requests with the key of the uEntity, inserted or to be inserted
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity has no id
+    pub fn set_requester(&mut self, entity: &UEntity) -> Result<(), UStatus> {
+        self.requester = Some(entity_id(Some(entity))?);
+        Ok(())
+    }
+}
+
+/// Signature of a message, and the id of its signer for a request
+pub(crate) struct MessageSignature {
+    pub(crate) signature: Vec<u8>,
+    pub(crate) signer: Option<u32>,
+}
+
+impl MessageSignature {
+    // Add the signature to the attachment of the message
+    pub(crate) fn attach(&self, attachment: &mut AttachmentBuilder) {
+        attachment.insert(SIGNATURE_KEY, self.signature.as_slice());
+        if let Some(signer) = self.signer {
+            attachment.insert(SIGNER_KEY, signer.to_be_bytes().as_slice());
+        }
+    }
+}
+
+/// Public keys of the trusted uEntities, and the signature policies of the topics
+pub struct TrustStore {
+    keys: HashMap<u32, VerifyingKey>,
+    // Policies by Zenoh key
+    policies: HashMap<String, SignaturePolicy>,
+    default_policy: SignaturePolicy,
+}
+
+impl TrustStore {
+    /// Create a trust store applying the policy to the topics without their own one
+    #[must_use]
+    pub fn new(default_policy: SignaturePolicy) -> TrustStore {
+        TrustStore {
+            keys: HashMap::new(),
+            policies: HashMap::new(),
+            default_policy,
+        }
+    }
+
+    /// Trust the messages of the uEntity signed with the key
+    ///
+    /// # Errors
+    /// Will return `Err` if the uEntity has no id
+    pub fn trust(&mut self, entity: &UEntity, key: VerifyingKey) -> Result<(), UStatus> {
+        self.keys.insert(entity_id(Some(entity))?, key);
+        Ok(())
+    }
+
+    /// Apply the policy to the messages of the topic or RPC method
+    ///
+    /// # Errors
+    /// Will return `Err` if the `UUri` can't be turned into a Zenoh key
+    pub fn set_policy(&mut self, uri: &UUri, policy: SignaturePolicy) -> Result<(), UStatus> {
+        self.policies
+            .insert(ULinkZenoh::to_zenoh_key_string(uri)?, policy);
+        Ok(())
+    }
+
+    fn policy(&self, zenoh_key: &str) -> SignaturePolicy {
+        self.policies
+            .get(zenoh_key)
+            .copied()
+            .unwrap_or(self.default_policy)
+    }
+}
+
+fn entity_id(entity: Option<&UEntity>) -> Result<u32, UStatus> {
+    entity
+        .and_then(|entity| entity.id)
+        .ok_or_else(|| UStatus::fail_with_code(UCode::InvalidArgument, "The uEntity needs an id"))
+}
+
+fn unauthenticated(message: &str) -> UStatus {
+    UStatus::fail_with_code(UCode::Unauthenticated, message)
+}
+
+// The Zenoh key, the length-prefixed UAttributes, the payload format and the payload
+fn signed_data(zenoh_key: &str, attributes: &[u8], payload: &UPayload) -> Option<Vec<u8>> {
+    let Some(Data::Value(buf)) = &payload.data else {
+        return None;
+    };
+    let attributes_len = u32::try_from(attributes.len()).ok()?;
+    let mut data = zenoh_key.as_bytes().to_vec();
+    data.push(0);
+    data.extend(attributes_len.to_be_bytes());
+    data.extend(attributes);
+    data.extend(payload.format.to_be_bytes());
+    data.extend(buf);
+    Some(data)
+}
+
+/// Sign the message if there is a key for the uEntity of the topic or RPC method,
+/// or for the requester when it's a request
+pub(crate) fn sign(
+    keys: Option<&SigningKeys>,
+    uri: &UUri,
+    payload: &UPayload,
+    attributes: &[u8],
+    request: bool,
+) -> Result<Option<MessageSignature>, UStatus> {
+    let Some(keys) = keys else {
+        return Ok(None);
+    };
+    let signer = if request {
+        keys.requester
+    } else {
+        entity_id(uri.entity.as_ref()).ok()
+    };
+    let Some(key) = signer.and_then(|id| keys.keys.get(&id)) else {
+        return Ok(None);
+    };
+    let zenoh_key = ULinkZenoh::to_zenoh_key_string(uri)?;
+    let data = signed_data(&zenoh_key, attributes, payload).ok_or_else(|| {
+        UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "Only payloads with a value can be signed",
+        )
+    })?;
+    Ok(Some(MessageSignature {
+        signature: key.sign(&data).to_vec(),
+        signer: signer.filter(|_| request),
+    }))
+}
+
+/// Check the signature of the message against the key trusted for the uEntity of its source,
+/// or for the requester of a request. The signature is checked over the serialized `UAttributes`
+/// of the attachment, as they were sent.
+///
+/// # Errors
+/// Will return `Err` with `Unauthenticated` if the message is rejected by the policy
+pub(crate) fn verify(
+    trust: Option<&TrustStore>,
+    msg: &UMessage,
+    attachment: Option<&Attachment>,
+) -> Result<(), UStatus> {
+    let Some(trust) = trust else {
+        return Ok(());
+    };
+    let Some(source) = &msg.source else {
+        return Err(unauthenticated("The message has no source"));
+    };
+    let zenoh_key = ULinkZenoh::to_zenoh_key_string(source)?;
+    let policy = trust.policy(&zenoh_key);
+    if policy == SignaturePolicy::Ignore {
+        return Ok(());
+    }
+    let Some(signature) =
+        attachment.and_then(|attachment| attachment.get(&SIGNATURE_KEY.as_bytes()))
+    else {
+        return match policy {
+            SignaturePolicy::Require => Err(unauthenticated("The message isn't signed")),
+            _ => Ok(()),
+        };
+    };
+    let signature = Signature::from_slice(&signature)
+        .map_err(|_| unauthenticated("The message signature is invalid"))?;
+    let request = msg
+        .attributes
+        .as_ref()
+        .is_some_and(|attributes| attributes.r#type == UMessageType::UmessageTypeRequest as i32);
+    // Only a request tells who signed it, the other messages are signed by their source
+    let signer = if request {
+        attachment
+            .and_then(|attachment| attachment.get(&SIGNER_KEY.as_bytes()))
+            .and_then(|signer| <[u8; 4]>::try_from(&*signer).ok())
+            .map(u32::from_be_bytes)
+    } else {
+        entity_id(source.entity.as_ref()).ok()
+    };
+    let key = signer
+        .and_then(|id| trust.keys.get(&id))
+        .ok_or_else(|| unauthenticated("The uEntity of the message isn't trusted"))?;
+    let attributes = attachment
+        .and_then(|attachment| attachment.get(&codec::UATTRIBUTES_KEY.as_bytes()))
+        .ok_or_else(|| unauthenticated("The message has no attributes"))?;
+    let data = msg
+        .payload
+        .as_ref()
+        .and_then(|payload| signed_data(&zenoh_key, &attributes, payload))
+        .ok_or_else(|| unauthenticated("The message payload can't be verified"))?;
+    key.verify(&data, &signature)
+        .map_err(|_| unauthenticated("The message signature doesn't match"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::uprotocol::{UAttributes, UPayloadFormat, UResource};
+    use zenoh::sample::AttachmentBuilder;
+
+    fn entity() -> UEntity {
+        UEntity {
+            name: "body.access".to_string(),
+            version_major: Some(1),
+            id: Some(1234),
+            ..Default::default()
+        }
+    }
+
+    fn message() -> UMessage {
+        UMessage {
+            source: Some(UUri {
+                entity: Some(entity()),
+                resource: Some(UResource {
+                    name: "door".to_string(),
+                    instance: Some("front_left".to_string()),
+                    message: Some("Door".to_string()),
+                    id: Some(5678),
+                }),
+                ..Default::default()
+            }),
+            attributes: Some(UAttributes {
+                ttl: Some(1000),
+                ..Default::default()
+            }),
+            payload: Some(UPayload {
+                length: Some(0),
+                format: UPayloadFormat::UpayloadFormatText as i32,
+                data: Some(Data::Value(b"Hello World!".to_vec())),
+            }),
+        }
+    }
+
+    fn signed_attachment(key: SigningKey, msg: &UMessage) -> Attachment {
+        let mut keys = SigningKeys::new();
+        keys.insert(&entity(), key).unwrap();
+        keys.set_requester(&entity()).unwrap();
+        let attributes = codec::encode_attributes(msg.attributes.as_ref().unwrap());
+        let request =
+            msg.attributes.as_ref().unwrap().r#type == UMessageType::UmessageTypeRequest as i32;
+        let signature = sign(
+            Some(&keys),
+            msg.source.as_ref().unwrap(),
+            msg.payload.as_ref().unwrap(),
+            &attributes,
+            request,
+        )
+        .unwrap()
+        .unwrap();
+        let mut attachment = codec::attachment(&attributes);
+        signature.attach(&mut attachment);
+        attachment.build()
+    }
+
+    #[test]
+    fn test_verify() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut trust = TrustStore::new(SignaturePolicy::Require);
+        trust.trust(&entity(), key.verifying_key()).unwrap();
+        let msg = message();
+        let attachment = signed_attachment(key, &msg);
+        assert!(verify(Some(&trust), &msg, Some(&attachment)).is_ok());
+
+        // Tampered payload
+        let mut tampered = message();
+        tampered.payload.as_mut().unwrap().data = Some(Data::Value(b"Hello Moon!".to_vec()));
+        let status = verify(Some(&trust), &tampered, Some(&attachment)).unwrap_err();
+        assert_eq!(status.code, UCode::Unauthenticated as i32);
+
+        // Signed with an untrusted key
+        let attachment = signed_attachment(SigningKey::from_bytes(&[2; 32]), &msg);
+        assert!(verify(Some(&trust), &msg, Some(&attachment)).is_err());
+    }
+
+    #[test]
+    fn test_verify_request() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut trust = TrustStore::new(SignaturePolicy::Require);
+        trust.trust(&entity(), key.verifying_key()).unwrap();
+        let mut msg = message();
+        msg.attributes.as_mut().unwrap().r#type = UMessageType::UmessageTypeRequest as i32;
+        let attachment = signed_attachment(key, &msg);
+        assert!(attachment.get(&SIGNER_KEY.as_bytes()).is_some());
+        assert!(verify(Some(&trust), &msg, Some(&attachment)).is_ok());
+
+        // The requester isn't trusted
+        let mut other_trust = TrustStore::new(SignaturePolicy::Require);
+        other_trust
+            .trust(
+                &UEntity {
+                    id: Some(4321),
+                    ..entity()
+                },
+                SigningKey::from_bytes(&[1; 32]).verifying_key(),
+            )
+            .unwrap();
+        assert!(verify(Some(&other_trust), &msg, Some(&attachment)).is_err());
+    }
+
+    #[test]
+    fn test_verify_raw_attributes() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut trust = TrustStore::new(SignaturePolicy::Require);
+        trust.trust(&entity(), key.verifying_key()).unwrap();
+        let msg = message();
+        let attachment = signed_attachment(key, &msg);
+
+        // The attributes sent are checked, not the decoded ones
+        let mut builder = codec::attachment(&codec::encode_attributes(&UAttributes {
+            ttl: Some(2000),
+            ..Default::default()
+        }));
+        builder.insert(
+            SIGNATURE_KEY,
+            &*attachment.get(&SIGNATURE_KEY.as_bytes()).unwrap(),
+        );
+        let tampered = builder.build();
+        assert!(verify(Some(&trust), &msg, Some(&tampered)).is_err());
+    }
+
+    #[test]
+    fn test_verify_unsigned() {
+        let key = SigningKey::from_bytes(&[1; 32]);
+        let mut trust = TrustStore::new(SignaturePolicy::Require);
+        trust.trust(&entity(), key.verifying_key()).unwrap();
+        let msg = message();
+        let unsigned = AttachmentBuilder::new().build();
+        assert!(verify(Some(&trust), &msg, Some(&unsigned)).is_err());
+
+        trust
+            .set_policy(msg.source.as_ref().unwrap(), SignaturePolicy::AllowUnsigned)
+            .unwrap();
+        assert!(verify(Some(&trust), &msg, Some(&unsigned)).is_ok());
+        let attachment = signed_attachment(SigningKey::from_bytes(&[2; 32]), &msg);
+        assert!(verify(Some(&trust), &msg, Some(&attachment)).is_err());
+
+        trust
+            .set_policy(msg.source.as_ref().unwrap(), SignaturePolicy::Ignore)
+            .unwrap();
+        assert!(verify(Some(&trust), &msg, Some(&attachment)).is_ok());
+    }
+}
@@ -0,0 +1,618 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Embedded storage of uProtocol topics.
+//!
+//! A [`Storage`] listens to the configured topic patterns and appends every received `UMessage`
+//! to segment files of [`crate::record`]s. It answers the Zenoh queries on these topics:
+//! * without parameters, with the latest message of each topic
+//! * with `_time=[<start>..<end>]` (milliseconds since the Unix epoch, each bound optional),
+//!   with all the messages received in the range
+//!
+//! The oldest segments are deleted once the age or size limit is exceeded.
+use crate::{
+    acl::Action,
+    cache, codec, crypto,
+    record::{self, Record, RecordReader},
+    signing, ULinkZenoh,
+};
+use prost::Message;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uprotocol_sdk::uprotocol::{Data, UCode, UMessage, UStatus, UUri};
+use zenoh::{
+    prelude::{r#async::AsyncResolve, *},
+    queryable::{Query, Queryable},
+    subscriber::Subscriber,
+};
+
+pub const SEGMENT_EXTENSION: &str = "urec";
+
+const DEFAULT_SEGMENT_SIZE: u64 = 1024 * 1024;
+const QUERY_TIMEOUT: Duration = Duration::from_millis(1000);
+
+pub struct StorageConfig {
+    /// Directory of the segment files
+    pub path: PathBuf,
+    /// Topic patterns to store, see [`topic_key_expr`]
+    pub topics: Vec<UUri>,
+    pub max_age: Option<Duration>,
+    /// Maximum size of all the segments, in bytes
+    pub max_size: Option<u64>,
+    /// Size from which a new segment is started, in bytes
+    pub segment_size: u64,
+}
+
+impl StorageConfig {
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        StorageConfig {
+            path: path.into(),
+            topics: vec![],
+            max_age: None,
+            max_size: None,
+            segment_size: DEFAULT_SEGMENT_SIZE,
+        }
+    }
+
+    #[must_use]
+    pub fn topic(mut self, pattern: UUri) -> Self {
+        self.topics.push(pattern);
+        self
+    }
+
+    #[must_use]
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    #[must_use]
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    #[must_use]
+    pub fn segment_size(mut self, segment_size: u64) -> Self {
+        self.segment_size = segment_size;
+        self
+    }
+}
+
+/// Zenoh key expression of a local topic pattern.
+/// The uEntity id, the major version and the resource id which aren't set match any value.
+///
+/// # Errors
+/// Will return `Err` if the pattern has an authority or an id out of the micro format range
+pub fn topic_key_expr(pattern: &UUri) -> Result<String, UStatus> {
+    if pattern.authority.is_some() {
+        return Err(UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "Only local topics can be stored",
+        ));
+    }
+    let out_of_range =
+        || UStatus::fail_with_code(UCode::InvalidArgument, "Id out of the micro format range");
+    let id = |id: Option<u32>| match id {
+        Some(id) => u16::try_from(id)
+            .map(|id| format!("{id:04x}"))
+            .map_err(|_| out_of_range()),
+        None => Ok("$*".to_string()),
+    };
+    let resource_id = id(pattern.resource.as_ref().and_then(|r| r.id))?;
+    let entity_id = id(pattern.entity.as_ref().and_then(|e| e.id))?;
+    let version_major = match pattern.entity.as_ref().and_then(|e| e.version_major) {
+        Some(version_major) => u8::try_from(version_major)
+            .map(|v| format!("{v:02x}"))
+            .map_err(|_| out_of_range())?,
+        None => "$*".to_string(),
+    };
+    // Same layout as the micro format: version, local authority, resource id, entity id,
+    // major version and a reserved byte
+    let mut key_expr = format!("0100{resource_id}{entity_id}{version_major}00");
+    while key_expr.contains("$*$*") {
+        key_expr = key_expr.replace("$*$*", "$*");
+    }
+    Ok(key_expr)
+}
+
+fn to_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |d| u64::try_from(d.as_millis()).unwrap_or(u64::MAX))
+}
+
+// Parse the `_time=[<start>..<end>]` parameter
+fn parse_time_range(parameters: &str) -> Result<Option<(u64, u64)>, UStatus> {
+    let invalid = || UStatus::fail_with_code(UCode::InvalidArgument, "Invalid _time parameter");
+    for parameter in parameters.split(['&', ';']) {
+        let Some(value) = parameter.strip_prefix("_time=") else {
+            continue;
+        };
+        let (start, end) = value
+            .strip_prefix('[')
+            .and_then(|v| v.strip_suffix(']'))
+            .and_then(|v| v.split_once(".."))
+            .ok_or_else(invalid)?;
+        let start = if start.is_empty() {
+            0
+        } else {
+            start.parse().map_err(|_| invalid())?
+        };
+        let end = if end.is_empty() {
+            u64::MAX
+        } else {
+            end.parse().map_err(|_| invalid())?
+        };
+        return Ok(Some((start, end)));
+    }
+    Ok(None)
+}
+
+struct Segment {
+    path: PathBuf,
+    // Reception time of the first and last records
+    first: u64,
+    last: u64,
+    size: u64,
+}
+
+// Segment files named after the time of their first record
+struct SegmentStore {
+    path: PathBuf,
+    segments: Vec<Segment>,
+    // The last segment, opened to append once something is stored
+    file: Option<File>,
+    max_age: Option<Duration>,
+    max_size: Option<u64>,
+    segment_size: u64,
+}
+
+impl SegmentStore {
+    fn open(config: &StorageConfig) -> io::Result<SegmentStore> {
+        fs::create_dir_all(&config.path)?;
+        let mut segments = vec![];
+        for entry in fs::read_dir(&config.path)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+            let Some(first) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse().ok())
+            else {
+                continue;
+            };
+            // A record truncated by a crash ends the segment
+            let last = RecordReader::new(BufReader::new(File::open(&path)?))
+                .map_while(Result::ok)
+                .last()
+                .map_or(first, |record| record.timestamp);
+            let size = fs::metadata(&path)?.len();
+            segments.push(Segment {
+                path,
+                first,
+                last,
+                size,
+            });
+        }
+        segments.sort_by_key(|segment| segment.first);
+        Ok(SegmentStore {
+            path: config.path.clone(),
+            segments,
+            file: None,
+            max_age: config.max_age,
+            max_size: config.max_size,
+            segment_size: config.segment_size,
+        })
+    }
+
+    fn append(&mut self, record: &Record) -> io::Result<()> {
+        // A new segment is started after opening, in case the last one ends with a truncated record
+        let rotate = match (&self.file, self.segments.last()) {
+            (Some(_), Some(segment)) => segment.size >= self.segment_size,
+            _ => true,
+        };
+        if rotate {
+            let mut path = self.path.join(record.timestamp.to_string());
+            path.set_extension(SEGMENT_EXTENSION);
+            self.file = Some(OpenOptions::new().create(true).append(true).open(&path)?);
+            self.segments.push(Segment {
+                path,
+                first: record.timestamp,
+                last: record.timestamp,
+                size: 0,
+            });
+        }
+        let segment = self.segments.last_mut().unwrap();
+        let written = record::write_record(self.file.as_mut().unwrap(), record)?;
+        segment.last = record.timestamp;
+        segment.size += u64::try_from(written).unwrap_or(u64::MAX);
+        self.enforce_retention()
+    }
+
+    // Delete the oldest segments, but never the one being written
+    fn enforce_retention(&mut self) -> io::Result<()> {
+        if let Some(max_age) = self.max_age {
+            let oldest = record::now_millis()
+                .saturating_sub(u64::try_from(max_age.as_millis()).unwrap_or(u64::MAX));
+            while self.segments.len() > 1 && self.segments[0].last < oldest {
+                fs::remove_file(self.segments.remove(0).path)?;
+            }
+        }
+        if let Some(max_size) = self.max_size {
+            while self.segments.len() > 1
+                && self.segments.iter().map(|s| s.size).sum::<u64>() > max_size
+            {
+                fs::remove_file(self.segments.remove(0).path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // The segments holding the time range, read once the store is released
+    fn range(&self, start: u64, end: u64) -> SegmentRange {
+        let start = match self.max_age {
+            Some(max_age) => start.max(
+                record::now_millis()
+                    .saturating_sub(u64::try_from(max_age.as_millis()).unwrap_or(u64::MAX)),
+            ),
+            None => start,
+        };
+        let segments = self
+            .segments
+            .iter()
+            .filter(|segment| segment.last >= start && segment.first <= end)
+            .map(|segment| (segment.path.clone(), segment.size))
+            .collect();
+        SegmentRange {
+            start,
+            end,
+            segments,
+        }
+    }
+}
+
+// Segments to read without blocking `append`, with their size when selected
+struct SegmentRange {
+    start: u64,
+    end: u64,
+    segments: Vec<(PathBuf, u64)>,
+}
+
+impl SegmentRange {
+    fn records(&self) -> io::Result<Vec<Record>> {
+        let mut records = vec![];
+        for (path, size) in &self.segments {
+            let file = match File::open(path) {
+                Ok(file) => file,
+                // Deleted by the retention meanwhile
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            // Only the records written when selected, not one being appended
+            for record in RecordReader::new(BufReader::new(file.take(*size))).map_while(Result::ok)
+            {
+                if (self.start..=self.end).contains(&record.timestamp) {
+                    records.push(record);
+                }
+            }
+        }
+        Ok(records)
+    }
+}
+
+// Answer the query with the stored messages
+fn reply(store: &Mutex<SegmentStore>, query: &Query) {
+    let Ok(range) = parse_time_range(query.parameters()) else {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(parameters = query.parameters(), "Invalid storage query");
+        return;
+    };
+    let (start, end) = range.unwrap_or((0, u64::MAX));
+    let segments = store.lock().unwrap().range(start, end);
+    let Ok(records) = segments.records() else {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("Unable to read the storage");
+        return;
+    };
+    let mut messages = vec![];
+    // Without time range, only the latest message of each topic
+    let mut latest: HashMap<String, (KeyExpr<'static>, Record)> = HashMap::new();
+    for record in records {
+        let Some(Ok(key)) = record
+            .message
+            .as_ref()
+            .and_then(|message| message.source.as_ref())
+            .map(ULinkZenoh::to_zenoh_key_string)
+        else {
+            continue;
+        };
+        let Ok(key_expr) = KeyExpr::try_from(key) else {
+            continue;
+        };
+        if !query.key_expr().intersects(&key_expr) {
+            continue;
+        }
+        if range.is_some() {
+            messages.push((key_expr, record));
+        } else {
+            latest.insert(key_expr.to_string(), (key_expr, record));
+        }
+    }
+    if range.is_none() {
+        messages = latest.into_values().collect();
+    }
+    for (key_expr, record) in messages {
+        let Some(message) = record.message else {
+            continue;
+        };
+        let Some(payload) = message.payload else {
+            continue;
+        };
+        let Some(Data::Value(buf)) = payload.data else {
+            continue;
+        };
+        // The attributes as sent, so that the signature still matches
+        let attributes = record
+            .attributes
+            .unwrap_or_else(|| message.attributes.unwrap_or_default().encode_to_vec());
+        cache::reply_message(
+            query,
+            key_expr,
+            buf,
+            payload.format,
+            &attributes,
+            record.signature.as_deref(),
+        );
+    }
+}
+
+/// Persist the topics matching the configured patterns, and answer the queries on them
+pub struct Storage {
+    store: Arc<Mutex<SegmentStore>>,
+    _subscribers: Vec<Subscriber<'static, ()>>,
+    _queryables: Vec<Queryable<'static, ()>>,
+}
+
+impl Storage {
+    /// Open the segments in the configured directory, and start storing the topics
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to open the directory, if a pattern is invalid, with
+    /// `PermissionDenied` if the access control of the uLink doesn't allow to subscribe to it,
+    /// or if unable to declare the Zenoh subscribers and queryables
+    pub async fn start(ulink: &ULinkZenoh, config: StorageConfig) -> Result<Storage, UStatus> {
+        for pattern in &config.topics {
+            ulink.check_access(Action::Subscribe, pattern)?;
+        }
+        let store = SegmentStore::open(&config).map_err(|e| {
+            UStatus::fail_with_code(UCode::Internal, &format!("Unable to open storage: {e}"))
+        })?;
+        let store = Arc::new(Mutex::new(store));
+        let mut subscribers = vec![];
+        let mut queryables = vec![];
+        for pattern in &config.topics {
+            let key_expr = topic_key_expr(pattern)?;
+
+            let store_cloned = store.clone();
+            let metrics = ulink.metrics.clone();
+            let subscriber = ulink
+                .session
+                .declare_subscriber(&key_expr)
+                .callback(move |sample: Sample| {
+                    let Ok(topic) = ULinkZenoh::from_zenoh_key_string(sample.key_expr.as_str())
+                    else {
+                        return;
+                    };
+                    let Ok(msg) = ULinkZenoh::sample_to_umessage(&topic, &sample, false) else {
+                        return;
+                    };
+                    // Keep what the signature covers
+                    let get = |key: &str| {
+                        sample
+                            .attachment()
+                            .and_then(|attachment| attachment.get(&key.as_bytes()))
+                            .map(|value| value.to_vec())
+                    };
+                    let record = Record {
+                        attributes: get(codec::UATTRIBUTES_KEY),
+                        signature: get(signing::SIGNATURE_KEY),
+                        ..Record::now(msg)
+                    };
+                    if let Err(e) = store_cloned.lock().unwrap().append(&record) {
+                        metrics.on_storage_failure();
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(error = %e, "Unable to write the storage");
+                        #[cfg(not(feature = "tracing"))]
+                        let _ = e;
+                    }
+                })
+                .res()
+                .await
+                .map_err(|_| {
+                    UStatus::fail_with_code(UCode::Internal, "Unable to declare storage subscriber")
+                })?;
+            subscribers.push(subscriber);
+
+            let store_cloned = store.clone();
+            let queryable = ulink
+                .session
+                .declare_queryable(&key_expr)
+                .callback(move |query: Query| reply(&store_cloned, &query))
+                .res()
+                .await
+                .map_err(|_| {
+                    UStatus::fail_with_code(UCode::Internal, "Unable to declare storage queryable")
+                })?;
+            queryables.push(queryable);
+        }
+        Ok(Storage {
+            store,
+            _subscribers: subscribers,
+            _queryables: queryables,
+        })
+    }
+
+    /// Size of all the segments, in bytes
+    #[must_use]
+    pub fn size(&self) -> u64 {
+        self.store
+            .lock()
+            .unwrap()
+            .segments
+            .iter()
+            .map(|segment| segment.size)
+            .sum()
+    }
+}
+
+/// Get the messages of the topic pattern received by the storages in the time range,
+/// ordered by creation time. The messages are stored as sent, so their signature is verified with
+/// the trust store of the uLink, and the ones of the encrypted topics are decrypted with its keys.
+///
+/// # Errors
+/// Will return `Err` if the pattern is invalid, with `PermissionDenied` if the access control of
+/// the uLink doesn't allow to subscribe to it, if unable to query with Zenoh, with
+/// `Unauthenticated` if a message is rejected by the trust store or with `PermissionDenied` if a
+/// message can't be decrypted
+pub async fn query(
+    ulink: &ULinkZenoh,
+    pattern: &UUri,
+    start: Option<SystemTime>,
+    end: Option<SystemTime>,
+) -> Result<Vec<UMessage>, UStatus> {
+    ulink.check_access(Action::Subscribe, pattern)?;
+    let selector = format!(
+        "{}?_time=[{}..{}]",
+        topic_key_expr(pattern)?,
+        start
+            .map(to_millis)
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+        end.map(to_millis)
+            .map(|t| t.to_string())
+            .unwrap_or_default(),
+    );
+    let replies = ulink
+        .session
+        .get(&selector)
+        .consolidation(ConsolidationMode::None)
+        .timeout(QUERY_TIMEOUT)
+        .res()
+        .await
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to query with Zenoh"))?;
+    let mut messages = vec![];
+    while let Ok(reply) = replies.recv_async().await {
+        let Ok(sample) = reply.sample else {
+            continue;
+        };
+        let Ok(topic) = ULinkZenoh::from_zenoh_key_string(sample.key_expr.as_str()) else {
+            continue;
+        };
+        if let Ok(msg) = ULinkZenoh::sample_to_umessage(&topic, &sample, false) {
+            signing::verify(ulink.trust_store.as_deref(), &msg, sample.attachment())?;
+            messages.push(crypto::open(ulink.keys.as_deref(), msg)?);
+        }
+    }
+    let id = |msg: &UMessage| {
+        msg.attributes
+            .as_ref()
+            .and_then(|attributes| attributes.id.as_ref())
+            .map(|id| (id.msb, id.lsb))
+    };
+    // Several storages may have the same messages
+    messages.sort_by_key(id);
+    messages.dedup_by_key(|msg| id(msg));
+    Ok(messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uprotocol_sdk::uprotocol::{UEntity, UResource};
+
+    #[test]
+    fn test_topic_key_expr() {
+        let mut uri = UUri {
+            entity: Some(UEntity {
+                name: "body.access".to_string(),
+                version_major: Some(1),
+                id: Some(1234),
+                ..Default::default()
+            }),
+            resource: Some(UResource {
+                name: "door".to_string(),
+                id: Some(5678),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(
+            topic_key_expr(&uri).unwrap(),
+            ULinkZenoh::to_zenoh_key_string(&uri).unwrap()
+        );
+        uri.resource = None;
+        assert_eq!(topic_key_expr(&uri).unwrap(), "0100$*04d20100");
+        uri.entity = None;
+        assert_eq!(topic_key_expr(&uri).unwrap(), "0100$*00");
+    }
+
+    #[test]
+    fn test_parse_time_range() {
+        assert_eq!(parse_time_range("").unwrap(), None);
+        assert_eq!(
+            parse_time_range("_time=[100..200]").unwrap(),
+            Some((100, 200))
+        );
+        assert_eq!(
+            parse_time_range("a=b&_time=[100..]").unwrap(),
+            Some((100, u64::MAX))
+        );
+        assert!(parse_time_range("_time=100..200").is_err());
+    }
+
+    #[test]
+    fn test_segment_retention() {
+        let path = std::env::temp_dir().join(format!(
+            "uprotocol_storage_test_{}_{}",
+            std::process::id(),
+            record::now_millis()
+        ));
+        let config = StorageConfig::new(&path).segment_size(1).max_size(20);
+        let mut store = SegmentStore::open(&config).unwrap();
+        for timestamp in 0..100 {
+            let record = Record {
+                timestamp,
+                message: Some(UMessage::default()),
+                ..Default::default()
+            };
+            store.append(&record).unwrap();
+        }
+        // One record per segment, only the last ones are kept
+        assert!(store.segments.len() < 100);
+        assert_eq!(store.segments.last().unwrap().first, 99);
+        let records = store.range(0, u64::MAX).records().unwrap();
+        assert_eq!(records.len(), store.segments.len());
+
+        // The segments are found again when reopening
+        let reopened = SegmentStore::open(&config).unwrap();
+        assert_eq!(reopened.segments.len(), store.segments.len());
+        fs::remove_dir_all(path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Test support, to run the uLinks in-process without waiting for the Zenoh discovery.
+//!
+//! The uLinks created with [`ULinkZenoh::from_session`] on one [`loopback_session`] deliver the
+//! messages and queries to each other locally, so a listener or RPC method is reachable as soon
+//! as it is registered. The `wait_*` helpers replace the fixed sleeps when the delivery is
+//! asynchronous, or when the uLinks really are on separate sessions.
+use crate::{liveliness::AliveKind, rt, ULinkZenoh};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uprotocol_sdk::uprotocol::{UCode, UStatus, UUri};
+use zenoh::{
+    config::{Config, EndPoint, WhatAmI},
+    prelude::r#async::*,
+};
+
+// Interval between the checks of the wait helpers
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Zenoh configuration of a peer without scouting, only listening on the loopback interface,
+/// so that it doesn't connect to anything by itself
+#[must_use]
+pub fn loopback_config() -> Config {
+    let mut config = Config::default();
+    // These valid values can't be rejected
+    let _ = config.set_mode(Some(WhatAmI::Peer));
+    let _ = config.scouting.multicast.set_enabled(Some(false));
+    let _ = config.scouting.gossip.set_enabled(Some(false));
+    if let Ok(endpoint) = "tcp/127.0.0.1:0".parse() {
+        config.listen.endpoints = vec![endpoint];
+    }
+    config
+}
+
+/// Open a Zenoh session with [`loopback_config`], to be shared by the uLinks of a test
+///
+/// # Errors
+/// Will return `Err` if unable to open the Zenoh session
+pub async fn loopback_session() -> Result<Arc<Session>, UStatus> {
+    open_session(loopback_config()).await
+}
+
+/// Open two Zenoh sessions connected to each other over the loopback interface, for the tests
+/// needing the uLinks on separate sessions without relying on the multicast scouting
+///
+/// # Errors
+/// Will return `Err` if unable to find a free port or to open the Zenoh sessions
+pub async fn connected_sessions() -> Result<(Arc<Session>, Arc<Session>), UStatus> {
+    let internal = |message: &str| UStatus::fail_with_code(UCode::Internal, message);
+    // The first session listens on a port free a moment ago, for the second one to connect to
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .map_err(|_| internal("Unable to find a free port"))?
+        .port();
+    let endpoint: EndPoint = format!("tcp/127.0.0.1:{port}")
+        .parse()
+        .map_err(|_| internal("Invalid endpoint"))?;
+    let mut listening = loopback_config();
+    listening.listen.endpoints = vec![endpoint.clone()];
+    let mut connecting = loopback_config();
+    connecting.connect.endpoints = vec![endpoint];
+    Ok((
+        open_session(listening).await?,
+        open_session(connecting).await?,
+    ))
+}
+
+async fn open_session(config: Config) -> Result<Arc<Session>, UStatus> {
+    zenoh::open(config)
+        .res_async()
+        .await
+        .map(Arc::new)
+        .map_err(|_| UStatus::fail_with_code(UCode::Internal, "Unable to open Zenoh session"))
+}
+
+/// Wait until the condition holds, returning `false` if it still doesn't after the timeout
+pub async fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let start = Instant::now();
+    while !condition() {
+        if start.elapsed() > timeout {
+            return false;
+        }
+        rt::sleep(POLL_INTERVAL).await;
+    }
+    true
+}
+
+/// Wait until the uLink is connected to at least one Zenoh peer
+///
+/// # Errors
+/// Will return `Err` with `DeadlineExceeded` if no peer is connected before the timeout
+pub async fn wait_for_peer(ulink: &ULinkZenoh, timeout: Duration) -> Result<(), UStatus> {
+    let start = Instant::now();
+    while ulink.session.info().peers_zid().res_async().await.count() == 0 {
+        if start.elapsed() > timeout {
+            return Err(UStatus::fail_with_code(
+                UCode::DeadlineExceeded,
+                "No Zenoh peer connected",
+            ));
+        }
+        rt::sleep(POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// Wait until the uEntity, RPC method or topic is seen alive by the uLink, see
+/// [`ULinkZenoh::wait_for_listener`] and [`ULinkZenoh::wait_for_rpc_server`]
+///
+/// # Errors
+/// Will return `Err` with `DeadlineExceeded` if it isn't before the timeout,
+/// or if unable to watch the liveliness tokens
+pub async fn wait_until_alive(
+    ulink: &ULinkZenoh,
+    kind: AliveKind,
+    uri: &UUri,
+    timeout: Duration,
+) -> Result<(), UStatus> {
+    ulink.wait_until_alive(kind, uri, timeout).await
+}
@@ -0,0 +1,250 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#[cfg(feature = "tracing")]
+use std::{
+    cell::Cell,
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "tracing")]
+use uprotocol_sdk::uprotocol::UAttributes;
+use uprotocol_sdk::uprotocol::Uuid;
+#[cfg(feature = "tracing")]
+use zenoh::sample::{Attachment, AttachmentBuilder};
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    // Trace continued by the messages sent from this thread
+    static CURRENT: Cell<Option<TraceContext>> = const { Cell::new(None) };
+}
+
+/// Attachment key used to carry the W3C `traceparent`
+pub const TRACEPARENT_KEY: &str = "traceparent";
+
+/// W3C trace-context (<https://www.w3.org/TR/trace-context/>) carried with a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: u128,
+    pub parent_id: u64,
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Build the context of a message.
+    /// The trace id comes from `reqid` (or `id`), so a request and its response share one trace.
+    #[must_use]
+    pub fn from_uuids(trace: &Uuid, parent: &Uuid) -> Option<TraceContext> {
+        let trace_id = (u128::from(trace.msb) << 64) | u128::from(trace.lsb);
+        let parent_id = parent.lsb;
+        if trace_id == 0 || parent_id == 0 {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            parent_id,
+            sampled: true,
+        })
+    }
+
+    /// Format as a version 00 `traceparent` header value
+    #[must_use]
+    pub fn to_traceparent(&self) -> String {
+        format!(
+            "00-{:032x}-{:016x}-{:02x}",
+            self.trace_id,
+            self.parent_id,
+            u8::from(self.sampled)
+        )
+    }
+
+    /// Parse a `traceparent` header value, returning `None` if it is malformed
+    #[must_use]
+    pub fn from_traceparent(traceparent: &str) -> Option<TraceContext> {
+        let mut fields = traceparent.trim().split('-');
+        let (Some(version), Some(trace_id), Some(parent_id), Some(flags)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return None;
+        };
+        // Version ff is forbidden, and version 00 doesn't allow extra fields
+        if version.len() != 2 || version == "ff" || (version == "00" && fields.next().is_some()) {
+            return None;
+        }
+        if trace_id.len() != 32 || parent_id.len() != 16 || flags.len() != 2 {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(trace_id, 16).ok()?;
+        let parent_id = u64::from_str_radix(parent_id, 16).ok()?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        if trace_id == 0 || parent_id == 0 {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            parent_id,
+            sampled: flags & 0x01 == 0x01,
+        })
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl TraceContext {
+    /// Make it the current context of the thread until the guard is dropped, so that the messages
+    /// sent meanwhile continue its trace
+    #[must_use]
+    pub fn enter(self) -> ContextGuard {
+        ContextGuard {
+            previous: CURRENT.with(|current| current.replace(Some(self))),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Get the current context of the thread, the one of the message being received while its
+    /// listener runs
+    #[must_use]
+    pub fn current() -> Option<TraceContext> {
+        CURRENT.with(Cell::get)
+    }
+}
+
+/// Guard of [`TraceContext::enter`], restoring the previous context when dropped
+#[cfg(feature = "tracing")]
+pub struct ContextGuard {
+    previous: Option<TraceContext>,
+    _not_send: PhantomData<*const ()>,
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for ContextGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| current.set(self.previous));
+    }
+}
+
+/// Future polled with the trace context as the current one, see [`with_context`]
+#[cfg(feature = "tracing")]
+pub struct WithContext<F> {
+    ctx: TraceContext,
+    future: Pin<Box<F>>,
+}
+
+#[cfg(feature = "tracing")]
+impl<F: Future> Future for WithContext<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let _guard = self.ctx.enter();
+        self.future.as_mut().poll(cx)
+    }
+}
+
+/// Run the future with the trace context as the current one, e.g. to send messages continuing
+/// the trace of a received one from a spawned task
+#[cfg(feature = "tracing")]
+pub fn with_context<F: Future>(ctx: TraceContext, future: F) -> WithContext<F> {
+    WithContext {
+        ctx,
+        future: Box::pin(future),
+    }
+}
+
+/// Get the trace context of a message sent by this uLink.
+/// It continues the current trace if any, otherwise the trace id comes from the message UUIDs.
+#[cfg(feature = "tracing")]
+pub(crate) fn context(attributes: &UAttributes) -> Option<TraceContext> {
+    let id = attributes.id.as_ref()?;
+    match TraceContext::current() {
+        Some(current) if id.lsb != 0 => Some(TraceContext {
+            parent_id: id.lsb,
+            ..current
+        }),
+        _ => TraceContext::from_uuids(attributes.reqid.as_ref().unwrap_or(id), id),
+    }
+}
+
+/// Add the `traceparent` of the message into the attachment
+#[cfg(feature = "tracing")]
+pub(crate) fn inject(attachment: &mut AttachmentBuilder, attributes: &UAttributes) {
+    if let Some(ctx) = context(attributes) {
+        attachment.insert(TRACEPARENT_KEY, ctx.to_traceparent().as_bytes());
+    }
+}
+
+/// Get the `traceparent` carried in the attachment
+#[cfg(feature = "tracing")]
+pub(crate) fn extract(attachment: Option<&Attachment>) -> Option<TraceContext> {
+    let value = attachment?.get(&TRACEPARENT_KEY.as_bytes())?;
+    TraceContext::from_traceparent(std::str::from_utf8(&value).ok()?)
+}
+
+/// Record the message identifiers and its trace into the span
+#[cfg(feature = "tracing")]
+pub(crate) fn record(span: &tracing::Span, attributes: &UAttributes, ctx: Option<TraceContext>) {
+    if let Some(id) = &attributes.id {
+        span.record("id", crate::ULinkZenoh::uuid_to_string(id).as_str());
+    }
+    if let Some(reqid) = &attributes.reqid {
+        span.record("reqid", crate::ULinkZenoh::uuid_to_string(reqid).as_str());
+    }
+    if let Some(ctx) = ctx {
+        span.record("trace_id", format!("{:032x}", ctx.trace_id).as_str());
+        span.record("parent_id", format!("{:016x}", ctx.parent_id).as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_roundtrip() {
+        let ctx = TraceContext::from_uuids(
+            &Uuid {
+                msb: 0x0123_4567_89ab_cdef,
+                lsb: 0x0011_2233_4455_6677,
+            },
+            &Uuid { msb: 1, lsb: 2 },
+        )
+        .unwrap();
+        let traceparent = ctx.to_traceparent();
+        assert_eq!(
+            traceparent,
+            "00-0123456789abcdef0011223344556677-0000000000000002-01"
+        );
+        assert_eq!(TraceContext::from_traceparent(&traceparent), Some(ctx));
+    }
+
+    #[test]
+    fn test_traceparent_invalid() {
+        assert_eq!(TraceContext::from_traceparent(""), None);
+        assert_eq!(
+            TraceContext::from_traceparent(
+                "00-00000000000000000000000000000000-0000000000000002-01"
+            ),
+            None
+        );
+        assert_eq!(
+            TraceContext::from_traceparent(
+                "ff-0123456789abcdef0011223344556677-0000000000000002-01"
+            ),
+            None
+        );
+        assert_eq!(
+            TraceContext::from_traceparent("00-0123456789abcdef-0000000000000002-01"),
+            None
+        );
+    }
+}
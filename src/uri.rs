@@ -0,0 +1,126 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! Parsing of the `UUri`s written by hand, in one of these forms:
+//! * long, e.g. `/body.access/1/door.front_left#Door`, which has no ids
+//! * micro, the hexadecimal micro format as in the Zenoh keys, e.g. `0100162e04d20100`
+//! * `[<name>@]<entity id>/<major version>/<resource id>`, e.g. `body.access@1234/1/5678`,
+//!   with decimal or `0x` hexadecimal ids, and `*` leaving a field unset so that a pattern
+//!   matches any value
+use uprotocol_sdk::{
+    uprotocol::{UEntity, UResource, UUri},
+    uri::serializer::{LongUriSerializer, MicroUriSerializer, UriSerializer},
+};
+
+fn parse_id(id: &str) -> Result<Option<u32>, String> {
+    if id == "*" {
+        return Ok(None);
+    }
+    let parsed = match id.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => id.parse(),
+    };
+    parsed.map(Some).map_err(|_| format!("Invalid id: {id}"))
+}
+
+/// Parse a `UUri` in any of the forms, e.g. as clap `value_parser`
+///
+/// # Errors
+/// Will return `Err` if it isn't in one of the forms
+pub fn parse_uri(uri: &str) -> Result<UUri, String> {
+    if uri.starts_with('/') {
+        return LongUriSerializer::deserialize(uri.to_string())
+            .map_err(|e| format!("Invalid long UUri {uri}: {e:?}"));
+    }
+    if !uri.contains('/') {
+        let micro = parse_hex(uri).map_err(|_| format!("Invalid micro UUri: {uri}"))?;
+        return MicroUriSerializer::deserialize(micro)
+            .map_err(|e| format!("Invalid micro UUri {uri}: {e:?}"));
+    }
+    let (name, ids) = uri.split_once('@').unwrap_or(("", uri));
+    let ids: Vec<&str> = ids.split('/').collect();
+    let [entity_id, version_major, resource_id] = ids[..] else {
+        return Err(format!(
+            "Expected [<name>@]<entity id>/<major version>/<resource id>: {uri}"
+        ));
+    };
+    let entity = UEntity {
+        name: name.to_string(),
+        id: parse_id(entity_id)?,
+        version_major: parse_id(version_major)?,
+        ..Default::default()
+    };
+    let resource = parse_id(resource_id)?.map(|id| UResource {
+        id: Some(id),
+        ..Default::default()
+    });
+    Ok(UUri {
+        entity: Some(entity),
+        resource,
+        ..Default::default()
+    })
+}
+
+/// Format a `UUri` in the `[<name>@]<entity id>/<major version>/<resource id>` form
+#[must_use]
+pub fn format_uri(uri: &UUri) -> String {
+    let id = |id: Option<u32>| id.map_or("*".to_string(), |id| id.to_string());
+    let entity = uri.entity.as_ref();
+    let name = entity.map_or("", |e| e.name.as_str());
+    format!(
+        "{}{}/{}/{}",
+        if name.is_empty() {
+            String::new()
+        } else {
+            format!("{name}@")
+        },
+        id(entity.and_then(|e| e.id)),
+        id(entity.and_then(|e| e.version_major)),
+        id(uri.resource.as_ref().and_then(|r| r.id)),
+    )
+}
+
+/// Parse the hexadecimal form of bytes
+///
+/// # Errors
+/// Will return `Err` if it isn't an even number of hexadecimal digits
+pub fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if hex.len() % 2 != 0 {
+        return Err(format!("Odd number of hexadecimal digits: {hex}"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| format!("Invalid hexadecimal: {hex}"))
+        })
+        .collect()
+}
+
+/// Whether the topic matches the pattern, the fields unset in the pattern matching any value
+#[must_use]
+pub fn matches(pattern: &UUri, topic: &UUri) -> bool {
+    let entity = pattern.entity.as_ref();
+    let topic_entity = topic.entity.as_ref();
+    let matches_id = |pattern: Option<u32>, id: Option<u32>| pattern.is_none() || pattern == id;
+    matches_id(entity.and_then(|e| e.id), topic_entity.and_then(|e| e.id))
+        && matches_id(
+            entity.and_then(|e| e.version_major),
+            topic_entity.and_then(|e| e.version_major),
+        )
+        && matches_id(
+            pattern.resource.as_ref().and_then(|r| r.id),
+            topic.resource.as_ref().and_then(|r| r.id),
+        )
+}
@@ -0,0 +1,723 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+//! uSubscription service over the Zenoh uLink.
+//!
+//! [`USubscriptionService`] serves the `Subscribe`, `Unsubscribe` and `FetchSubscribers` RPC
+//! methods, keeps the subscriptions in memory (optionally persisted through a
+//! [`SubscriptionPersistence`]) and publishes an [`Update`] on [`notification_topic`] for every
+//! change. Consumers use [`USubscriptionClient`] instead of calling `register_listener` directly.
+use crate::{handler, ULinkZenoh};
+use prost::Message;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use uprotocol_sdk::{
+    rpc::RpcServer,
+    transport::{builder::UAttributesBuilder, datamodel::UTransport},
+    uprotocol::{UCode, UEntity, UMessage, UPriority, UResource, UStatus, UUri},
+    uri::builder::resourcebuilder::UResourceBuilder,
+    uuid::builder::UUIDv8Builder,
+};
+
+pub const USUBSCRIPTION_NAME: &str = "core.usubscription";
+pub const USUBSCRIPTION_ID: u32 = 0;
+pub const USUBSCRIPTION_VERSION: u32 = 3;
+pub const SUBSCRIBE_ID: u32 = 1;
+pub const UNSUBSCRIBE_ID: u32 = 2;
+pub const FETCH_SUBSCRIBERS_ID: u32 = 8;
+pub const NOTIFICATION_ID: u32 = 0x8000;
+
+// TTL of the requests of the client, in milliseconds
+const REQUEST_TTL: i32 = 1000;
+
+/// State of a subscription, as defined in `core/usubscription/v3/usubscription.proto`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum SubscriptionState {
+    Unsubscribed = 0,
+    SubscribePending = 1,
+    Subscribed = 2,
+    UnsubscribePending = 3,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SubscriberInfo {
+    #[prost(message, optional, tag = "1")]
+    pub uri: Option<UUri>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SubscriptionStatus {
+    #[prost(enumeration = "SubscriptionState", tag = "1")]
+    pub state: i32,
+    #[prost(int32, tag = "2")]
+    pub code: i32,
+    #[prost(string, tag = "3")]
+    pub message: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SubscriptionRequest {
+    #[prost(message, optional, tag = "1")]
+    pub topic: Option<UUri>,
+    #[prost(message, optional, tag = "2")]
+    pub subscriber: Option<SubscriberInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct SubscriptionResponse {
+    #[prost(message, optional, tag = "1")]
+    pub status: Option<SubscriptionStatus>,
+    #[prost(message, optional, tag = "3")]
+    pub topic: Option<UUri>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UnsubscribeRequest {
+    #[prost(message, optional, tag = "1")]
+    pub topic: Option<UUri>,
+    #[prost(message, optional, tag = "2")]
+    pub subscriber: Option<SubscriberInfo>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FetchSubscribersRequest {
+    #[prost(message, optional, tag = "1")]
+    pub topic: Option<UUri>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FetchSubscribersResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub subscribers: Vec<SubscriberInfo>,
+    #[prost(message, optional, tag = "2")]
+    pub status: Option<UStatus>,
+}
+
+/// Notification published on [`notification_topic`] when a subscription changes
+#[derive(Clone, PartialEq, Message)]
+pub struct Update {
+    #[prost(message, optional, tag = "1")]
+    pub topic: Option<UUri>,
+    #[prost(message, optional, tag = "2")]
+    pub subscriber: Option<SubscriberInfo>,
+    #[prost(message, optional, tag = "3")]
+    pub status: Option<SubscriptionStatus>,
+}
+
+fn usubscription_entity() -> UEntity {
+    UEntity {
+        name: USUBSCRIPTION_NAME.to_string(),
+        id: Some(USUBSCRIPTION_ID),
+        version_major: Some(USUBSCRIPTION_VERSION),
+        ..Default::default()
+    }
+}
+
+fn method(name: &str, id: u32) -> UUri {
+    UUri {
+        entity: Some(usubscription_entity()),
+        resource: Some(UResourceBuilder::for_rpc_request(
+            Some(name.to_string()),
+            Some(id),
+        )),
+        ..Default::default()
+    }
+}
+
+#[must_use]
+pub fn subscribe_method() -> UUri {
+    method("Subscribe", SUBSCRIBE_ID)
+}
+
+#[must_use]
+pub fn unsubscribe_method() -> UUri {
+    method("Unsubscribe", UNSUBSCRIBE_ID)
+}
+
+#[must_use]
+pub fn fetch_subscribers_method() -> UUri {
+    method("FetchSubscribers", FETCH_SUBSCRIBERS_ID)
+}
+
+/// Topic of the subscription change notifications
+#[must_use]
+pub fn notification_topic() -> UUri {
+    UUri {
+        entity: Some(usubscription_entity()),
+        resource: Some(UResource {
+            name: "subscriptions".to_string(),
+            instance: None,
+            message: Some("Update".to_string()),
+            id: Some(NOTIFICATION_ID),
+        }),
+        ..Default::default()
+    }
+}
+
+fn ok_status() -> UStatus {
+    UStatus {
+        code: UCode::Ok as i32,
+        ..Default::default()
+    }
+}
+
+/// Storage of the subscriptions surviving a restart of the service
+pub trait SubscriptionPersistence: Send + Sync {
+    /// Load the subscriptions, as `(topic, subscriber)` pairs
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to read the storage
+    fn load(&self) -> Result<Vec<(UUri, SubscriberInfo)>, UStatus>;
+
+    /// # Errors
+    /// Will return `Err` if unable to write the storage
+    fn save(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<(), UStatus>;
+
+    /// # Errors
+    /// Will return `Err` if unable to write the storage
+    fn remove(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<(), UStatus>;
+}
+
+/// Keep the subscriptions in memory only
+pub struct NoPersistence;
+
+impl SubscriptionPersistence for NoPersistence {
+    fn load(&self) -> Result<Vec<(UUri, SubscriberInfo)>, UStatus> {
+        Ok(vec![])
+    }
+
+    fn save(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+        Ok(())
+    }
+
+    fn remove(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+        Ok(())
+    }
+}
+
+/// In-memory subscriptions, keyed by the Zenoh key of the topic
+pub struct SubscriptionStore {
+    subscriptions: Mutex<HashMap<String, (UUri, Vec<SubscriberInfo>)>>,
+    persistence: Box<dyn SubscriptionPersistence>,
+}
+
+impl SubscriptionStore {
+    /// # Errors
+    /// Will return `Err` if unable to load the persisted subscriptions
+    pub fn new(persistence: Box<dyn SubscriptionPersistence>) -> Result<Self, UStatus> {
+        let store = SubscriptionStore {
+            subscriptions: Mutex::new(HashMap::new()),
+            persistence,
+        };
+        for (topic, subscriber) in store.persistence.load()? {
+            store.insert(&topic, &subscriber)?;
+        }
+        Ok(store)
+    }
+
+    // Return whether the subscriber is new
+    fn insert(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<bool, UStatus> {
+        let key = ULinkZenoh::to_zenoh_key_string(topic)?;
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let (_, subscribers) = subscriptions
+            .entry(key)
+            .or_insert_with(|| (topic.clone(), vec![]));
+        if subscribers.contains(subscriber) {
+            return Ok(false);
+        }
+        subscribers.push(subscriber.clone());
+        Ok(true)
+    }
+
+    // Return whether the subscriber was there
+    fn discard(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<bool, UStatus> {
+        let key = ULinkZenoh::to_zenoh_key_string(topic)?;
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        let Some((_, subscribers)) = subscriptions.get_mut(&key) else {
+            return Ok(false);
+        };
+        let len = subscribers.len();
+        subscribers.retain(|s| s != subscriber);
+        let removed = subscribers.len() != len;
+        if subscribers.is_empty() {
+            subscriptions.remove(&key);
+        }
+        Ok(removed)
+    }
+
+    /// Add the subscriber to the topic, return whether it wasn't subscribed yet
+    ///
+    /// # Errors
+    /// Will return `Err` if the topic is invalid or unable to persist the subscription, the
+    /// subscriber being left unsubscribed
+    pub fn subscribe(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<bool, UStatus> {
+        let added = self.insert(topic, subscriber)?;
+        if added {
+            if let Err(e) = self.persistence.save(topic, subscriber) {
+                self.discard(topic, subscriber)?;
+                return Err(e);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Remove the subscriber from the topic, return whether it was subscribed
+    ///
+    /// # Errors
+    /// Will return `Err` if the topic is invalid or unable to persist the removal, the
+    /// subscriber being left subscribed
+    pub fn unsubscribe(&self, topic: &UUri, subscriber: &SubscriberInfo) -> Result<bool, UStatus> {
+        let removed = self.discard(topic, subscriber)?;
+        if removed {
+            if let Err(e) = self.persistence.remove(topic, subscriber) {
+                self.insert(topic, subscriber)?;
+                return Err(e);
+            }
+        }
+        Ok(removed)
+    }
+
+    /// # Errors
+    /// Will return `Err` if the topic is invalid
+    pub fn subscribers(&self, topic: &UUri) -> Result<Vec<SubscriberInfo>, UStatus> {
+        let key = ULinkZenoh::to_zenoh_key_string(topic)?;
+        Ok(self
+            .subscriptions
+            .lock()
+            .unwrap()
+            .get(&key)
+            .map(|(_, subscribers)| subscribers.clone())
+            .unwrap_or_default())
+    }
+}
+
+/// Serve the uSubscription service through a `ULinkZenoh`
+pub struct USubscriptionService {
+    ulink: Arc<ULinkZenoh>,
+    store: Arc<SubscriptionStore>,
+    listeners: Vec<(UUri, String)>,
+}
+
+impl USubscriptionService {
+    /// Start serving the uSubscription RPC methods
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to load the subscriptions or to register the RPC listeners
+    pub async fn start(
+        ulink: &Arc<ULinkZenoh>,
+        persistence: Box<dyn SubscriptionPersistence>,
+    ) -> Result<USubscriptionService, UStatus> {
+        let store = Arc::new(SubscriptionStore::new(persistence)?);
+        let mut listeners = vec![];
+
+        let store_cloned = store.clone();
+        let listener =
+            handler::register_rpc_handler(ulink, subscribe_method(), move |ulink, payload| {
+                let store = store_cloned.clone();
+                async move {
+                    Ok(handler::protobuf_payload(
+                        &subscribe(&ulink, &store, handler::decode_payload(payload)).await,
+                    ))
+                }
+            })
+            .await?;
+        listeners.push((subscribe_method(), listener));
+
+        let store_cloned = store.clone();
+        let listener =
+            handler::register_rpc_handler(ulink, unsubscribe_method(), move |ulink, payload| {
+                let store = store_cloned.clone();
+                async move {
+                    Ok(handler::protobuf_payload(
+                        &unsubscribe(&ulink, &store, handler::decode_payload(payload)).await,
+                    ))
+                }
+            })
+            .await?;
+        listeners.push((unsubscribe_method(), listener));
+
+        let store_cloned = store.clone();
+        let listener =
+            handler::register_rpc_handler(ulink, fetch_subscribers_method(), move |_, payload| {
+                let response = fetch_subscribers(&store_cloned, handler::decode_payload(payload));
+                async move { Ok(handler::protobuf_payload(&response)) }
+            })
+            .await?;
+        listeners.push((fetch_subscribers_method(), listener));
+
+        Ok(USubscriptionService {
+            ulink: ulink.clone(),
+            store,
+            listeners,
+        })
+    }
+
+    #[must_use]
+    pub fn store(&self) -> &SubscriptionStore {
+        &self.store
+    }
+
+    /// Stop serving the uSubscription RPC methods
+    ///
+    /// # Errors
+    /// Will return `Err` if unable to unregister the RPC listeners
+    pub async fn stop(self) -> Result<(), UStatus> {
+        for (method, listener) in self.listeners {
+            self.ulink
+                .unregister_rpc_listener(method, &listener)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+async fn notify(
+    ulink: &ULinkZenoh,
+    topic: UUri,
+    subscriber: SubscriberInfo,
+    state: SubscriptionState,
+) {
+    let update = Update {
+        topic: Some(topic),
+        subscriber: Some(subscriber),
+        status: Some(SubscriptionStatus {
+            state: state as i32,
+            code: UCode::Ok as i32,
+            message: String::new(),
+        }),
+    };
+    let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+    let _ = ulink
+        .send(
+            notification_topic(),
+            handler::protobuf_payload(&update),
+            attributes,
+        )
+        .await;
+}
+
+async fn subscribe(
+    ulink: &ULinkZenoh,
+    store: &SubscriptionStore,
+    request: Option<SubscriptionRequest>,
+) -> SubscriptionResponse {
+    let (Some(topic), Some(subscriber)) = request
+        .map(|request| (request.topic, request.subscriber))
+        .unwrap_or_default()
+    else {
+        return SubscriptionResponse {
+            status: Some(SubscriptionStatus {
+                state: SubscriptionState::Unsubscribed as i32,
+                code: UCode::InvalidArgument as i32,
+                message: "The request needs a topic and a subscriber".to_string(),
+            }),
+            topic: None,
+        };
+    };
+    let status = match store.subscribe(&topic, &subscriber) {
+        Ok(added) => {
+            if added {
+                notify(
+                    ulink,
+                    topic.clone(),
+                    subscriber,
+                    SubscriptionState::Subscribed,
+                )
+                .await;
+            }
+            SubscriptionStatus {
+                state: SubscriptionState::Subscribed as i32,
+                code: UCode::Ok as i32,
+                message: String::new(),
+            }
+        }
+        Err(status) => SubscriptionStatus {
+            state: SubscriptionState::Unsubscribed as i32,
+            code: status.code,
+            message: status.message.unwrap_or_default(),
+        },
+    };
+    SubscriptionResponse {
+        status: Some(status),
+        topic: Some(topic),
+    }
+}
+
+async fn unsubscribe(
+    ulink: &ULinkZenoh,
+    store: &SubscriptionStore,
+    request: Option<UnsubscribeRequest>,
+) -> UStatus {
+    let (Some(topic), Some(subscriber)) = request
+        .map(|request| (request.topic, request.subscriber))
+        .unwrap_or_default()
+    else {
+        return UStatus::fail_with_code(
+            UCode::InvalidArgument,
+            "The request needs a topic and a subscriber",
+        );
+    };
+    match store.unsubscribe(&topic, &subscriber) {
+        Ok(true) => {
+            notify(ulink, topic, subscriber, SubscriptionState::Unsubscribed).await;
+            ok_status()
+        }
+        Ok(false) => UStatus::fail_with_code(UCode::NotFound, "Subscription doesn't exist"),
+        Err(status) => status,
+    }
+}
+
+fn fetch_subscribers(
+    store: &SubscriptionStore,
+    request: Option<FetchSubscribersRequest>,
+) -> FetchSubscribersResponse {
+    let Some(topic) = request.and_then(|request| request.topic) else {
+        return FetchSubscribersResponse {
+            subscribers: vec![],
+            status: Some(UStatus::fail_with_code(
+                UCode::InvalidArgument,
+                "The request needs a topic",
+            )),
+        };
+    };
+    match store.subscribers(&topic) {
+        Ok(subscribers) => FetchSubscribersResponse {
+            subscribers,
+            status: Some(ok_status()),
+        },
+        Err(status) => FetchSubscribersResponse {
+            subscribers: vec![],
+            status: Some(status),
+        },
+    }
+}
+
+/// Client of the uSubscription service
+pub struct USubscriptionClient<'a> {
+    ulink: &'a ULinkZenoh,
+    subscriber: SubscriberInfo,
+}
+
+impl<'a> USubscriptionClient<'a> {
+    /// `subscriber` is the `UUri` of the local uEntity subscribing
+    #[must_use]
+    pub fn new(ulink: &'a ULinkZenoh, subscriber: UUri) -> Self {
+        USubscriptionClient {
+            ulink,
+            subscriber: SubscriberInfo {
+                uri: Some(subscriber),
+            },
+        }
+    }
+
+    async fn call<Req: Message, Resp: Message + Default>(
+        &self,
+        method: UUri,
+        request: &Req,
+    ) -> Result<Resp, UStatus> {
+        let attributes =
+            UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), REQUEST_TTL)
+                .with_reqid(UUIDv8Builder::new().build())
+                .build();
+        let response = self
+            .ulink
+            .invoke_method_message(method, handler::protobuf_payload(request), attributes)
+            .await?;
+        handler::decode_payload(handler::response_payload(response)?).ok_or(
+            UStatus::fail_with_code(UCode::Internal, "Unable to decode uSubscription response"),
+        )
+    }
+
+    /// Subscribe to the topic through uSubscription, then register the listener
+    ///
+    /// # Errors
+    /// Will return `Err` if uSubscription refuses the subscription or the registration fails
+    pub async fn subscribe(
+        &self,
+        topic: UUri,
+        listener: Box<dyn Fn(Result<UMessage, UStatus>) + Send + Sync + 'static>,
+    ) -> Result<String, UStatus> {
+        let request = SubscriptionRequest {
+            topic: Some(topic.clone()),
+            subscriber: Some(self.subscriber.clone()),
+        };
+        let response: SubscriptionResponse = self.call(subscribe_method(), &request).await?;
+        match response.status {
+            Some(status) if status.state == SubscriptionState::Subscribed as i32 => {}
+            Some(status) => {
+                return Err(UStatus::fail_with_code(
+                    UCode::try_from(status.code).unwrap_or(UCode::Unknown),
+                    &status.message,
+                ))
+            }
+            None => {
+                return Err(UStatus::fail_with_code(
+                    UCode::Internal,
+                    "Missing subscription status",
+                ))
+            }
+        }
+        self.ulink.register_listener(topic, listener).await
+    }
+
+    /// Unregister the listener, then unsubscribe from the topic through uSubscription
+    ///
+    /// # Errors
+    /// Will return `Err` if the listener doesn't exist or uSubscription refuses to unsubscribe
+    pub async fn unsubscribe(&self, topic: UUri, listener: &str) -> Result<(), UStatus> {
+        self.ulink
+            .unregister_listener(topic.clone(), listener)
+            .await?;
+        let request = UnsubscribeRequest {
+            topic: Some(topic),
+            subscriber: Some(self.subscriber.clone()),
+        };
+        let status: UStatus = self.call(unsubscribe_method(), &request).await?;
+        if status.code == UCode::Ok as i32 {
+            Ok(())
+        } else {
+            Err(status)
+        }
+    }
+
+    /// Get the subscribers of the topic
+    ///
+    /// # Errors
+    /// Will return `Err` if the RPC call fails
+    pub async fn fetch_subscribers(&self, topic: UUri) -> Result<Vec<UUri>, UStatus> {
+        let request = FetchSubscribersRequest { topic: Some(topic) };
+        let response: FetchSubscribersResponse =
+            self.call(fetch_subscribers_method(), &request).await?;
+        match response.status {
+            Some(status) if status.code != UCode::Ok as i32 => Err(status),
+            _ => Ok(response
+                .subscribers
+                .into_iter()
+                .filter_map(|subscriber| subscriber.uri)
+                .collect()),
+        }
+    }
+}
+
+/// Register a listener of the subscription changes, so a producer learns about its consumers
+///
+/// # Errors
+/// Same as [`UTransport::register_listener`]
+pub async fn register_update_listener(
+    ulink: &ULinkZenoh,
+    listener: Box<dyn Fn(Result<Update, UStatus>) + Send + Sync + 'static>,
+) -> Result<String, UStatus> {
+    ulink
+        .register_listener(
+            notification_topic(),
+            Box::new(move |result: Result<UMessage, UStatus>| {
+                listener(result.and_then(|msg| {
+                    handler::decode_payload(msg.payload).ok_or(UStatus::fail_with_code(
+                        UCode::Internal,
+                        "Unable to decode subscription update",
+                    ))
+                }));
+            }),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn topic(id: u32) -> UUri {
+        UUri {
+            entity: Some(UEntity {
+                name: "body.access".to_string(),
+                version_major: Some(1),
+                id: Some(1234),
+                ..Default::default()
+            }),
+            resource: Some(UResource {
+                name: "door".to_string(),
+                instance: Some("front_left".to_string()),
+                message: Some("Door".to_string()),
+                id: Some(id),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_subscription_store() {
+        let store = SubscriptionStore::new(Box::new(NoPersistence)).unwrap();
+        let subscriber = SubscriberInfo {
+            uri: Some(UUri {
+                entity: Some(UEntity {
+                    name: "dashboard.app".to_string(),
+                    version_major: Some(1),
+                    id: Some(42),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+        };
+        assert!(store.subscribe(&topic(1), &subscriber).unwrap());
+        assert!(!store.subscribe(&topic(1), &subscriber).unwrap());
+        assert_eq!(
+            store.subscribers(&topic(1)).unwrap(),
+            vec![subscriber.clone()]
+        );
+        assert!(store.subscribers(&topic(2)).unwrap().is_empty());
+        assert!(store.unsubscribe(&topic(1), &subscriber).unwrap());
+        assert!(!store.unsubscribe(&topic(1), &subscriber).unwrap());
+        assert!(store.subscribers(&topic(1)).unwrap().is_empty());
+    }
+
+    // Persistence whose writes all fail
+    struct FailingPersistence;
+
+    impl SubscriptionPersistence for FailingPersistence {
+        fn load(&self) -> Result<Vec<(UUri, SubscriberInfo)>, UStatus> {
+            Ok(vec![])
+        }
+
+        fn save(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+            Err(UStatus::fail_with_code(UCode::Unavailable, "Disk full"))
+        }
+
+        fn remove(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+            Err(UStatus::fail_with_code(UCode::Unavailable, "Disk full"))
+        }
+    }
+
+    #[test]
+    fn test_subscription_store_failing_persistence() {
+        let store = SubscriptionStore::new(Box::new(FailingPersistence)).unwrap();
+        let subscriber = SubscriberInfo {
+            uri: Some(topic(42)),
+        };
+        // What can't be persisted isn't kept in memory either
+        assert!(store.subscribe(&topic(1), &subscriber).is_err());
+        assert!(store.subscribers(&topic(1)).unwrap().is_empty());
+
+        store.insert(&topic(1), &subscriber).unwrap();
+        assert!(store.unsubscribe(&topic(1), &subscriber).is_err());
+        assert_eq!(store.subscribers(&topic(1)).unwrap(), vec![subscriber]);
+    }
+
+    #[test]
+    fn test_notification_topic() {
+        assert_eq!(
+            ULinkZenoh::to_zenoh_key_string(&notification_topic()).unwrap(),
+            "0100800000000300"
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use proptest::{option, prelude::*, test_runner::TestRunner};
+use std::time;
+use uprotocol_sdk::{
+    transport::builder::UAttributesBuilder,
+    uprotocol::{
+        Data, UAttributes, UEntity, UPayload, UPayloadFormat, UPriority, UResource, UUri, Uuid,
+    },
+};
+use uprotocol_zenoh_rust::{blocking, codec, testing};
+use zenoh::prelude::Encoding;
+
+fn uuid() -> impl Strategy<Value = Uuid> {
+    (any::<u64>(), any::<u64>()).prop_map(|(msb, lsb)| Uuid { msb, lsb })
+}
+
+fn uri() -> impl Strategy<Value = UUri> {
+    ("[a-z.]{1,16}", any::<u32>(), any::<u32>()).prop_map(|(name, id, version_major)| UUri {
+        entity: Some(UEntity {
+            name,
+            id: Some(id),
+            version_major: Some(version_major),
+            ..Default::default()
+        }),
+        ..Default::default()
+    })
+}
+
+prop_compose! {
+    fn attributes()(
+        id in option::of(uuid()),
+        r#type in any::<i32>(),
+        sink in option::of(uri()),
+        priority in any::<i32>(),
+        ttl in option::of(any::<i32>()),
+        permission_level in option::of(any::<i32>()),
+        commstatus in option::of(any::<i32>()),
+        reqid in option::of(uuid()),
+        token in option::of(".*"),
+    ) -> UAttributes {
+        UAttributes {
+            id,
+            r#type,
+            sink,
+            priority,
+            ttl,
+            permission_level,
+            commstatus,
+            reqid,
+            token,
+        }
+    }
+}
+
+// The attributes the uLink accepts to publish, and doesn't drop as expired
+prop_compose! {
+    fn publish_attributes()(
+        priority in prop::sample::select(vec![
+            UPriority::UpriorityCs0,
+            UPriority::UpriorityCs1,
+            UPriority::UpriorityCs2,
+            UPriority::UpriorityCs3,
+            UPriority::UpriorityCs4,
+            UPriority::UpriorityCs5,
+            UPriority::UpriorityCs6,
+        ]),
+        ttl in option::of(60_000..i32::MAX),
+        permission_level in option::of(any::<i32>()),
+        token in option::of(".*"),
+    ) -> UAttributes {
+        UAttributes {
+            ttl,
+            permission_level,
+            token,
+            ..UAttributesBuilder::publish(priority).build()
+        }
+    }
+}
+
+prop_compose! {
+    fn payload()(
+        format in prop::sample::select(vec![
+            UPayloadFormat::UpayloadFormatUnspecified,
+            UPayloadFormat::UpayloadFormatProtobuf,
+            UPayloadFormat::UpayloadFormatJson,
+            UPayloadFormat::UpayloadFormatSomeip,
+            UPayloadFormat::UpayloadFormatSomeipTlv,
+            UPayloadFormat::UpayloadFormatRaw,
+            UPayloadFormat::UpayloadFormatText,
+        ]),
+        data in prop::collection::vec(any::<u8>(), 0..1024),
+    ) -> UPayload {
+        UPayload {
+            length: Some(0),
+            format: format as i32,
+            data: Some(Data::Value(data)),
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_decode_never_panics(buf in prop::collection::vec(any::<u8>(), 0..256), suffix in ".*") {
+        let _ = codec::decode_attributes(&buf);
+        let _ = codec::decode_format_suffix(&suffix);
+        let _ = codec::decode_message(&buf, &Encoding::from(suffix), &buf);
+    }
+
+    #[test]
+    fn test_codec_round_trip(
+        attributes in attributes(),
+        format in any::<i32>(),
+        data in prop::collection::vec(any::<u8>(), 0..256),
+    ) {
+        let attachment = codec::attachment(&codec::encode_attributes(&attributes)).build();
+        prop_assert_eq!(codec::attachment_attributes(Some(&attachment)), Ok(attributes.clone()));
+
+        let (decoded, payload) = codec::decode_message(
+            &codec::encode_attributes(&attributes),
+            &codec::encoding(format),
+            &data,
+        )
+        .unwrap();
+        prop_assert_eq!(decoded, attributes);
+        prop_assert_eq!(payload.format, format);
+        prop_assert_eq!(payload.data, Some(Data::Value(data)));
+    }
+}
+
+#[test]
+fn test_send_receive_round_trip() {
+    let ulinkzenoh = blocking::ULinkZenoh::new(testing::loopback_config()).unwrap();
+    let topic = UUri {
+        entity: Some(UEntity {
+            name: "body.access".to_string(),
+            version_major: Some(1),
+            id: Some(1234),
+            ..Default::default()
+        }),
+        resource: Some(UResource {
+            name: "door".to_string(),
+            instance: Some("front_left".to_string()),
+            message: Some("Door".to_string()),
+            id: Some(5678),
+        }),
+        ..Default::default()
+    };
+    let receiver = ulinkzenoh.subscribe(topic.clone()).unwrap();
+
+    let mut runner = TestRunner::new(ProptestConfig::with_cases(64));
+    runner
+        .run(
+            &(publish_attributes(), payload()),
+            |(attributes, payload)| {
+                ulinkzenoh
+                    .send(topic.clone(), payload.clone(), attributes.clone())
+                    .unwrap();
+                let msg = receiver
+                    .recv_timeout(time::Duration::from_secs(5))
+                    .unwrap()
+                    .unwrap();
+                prop_assert_eq!(msg.source, Some(topic.clone()));
+                prop_assert_eq!(msg.attributes, Some(attributes));
+                prop_assert_eq!(msg.payload, Some(payload));
+                Ok(())
+            },
+        )
+        .unwrap();
+}
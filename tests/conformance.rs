@@ -0,0 +1,81 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+use std::sync::Arc;
+use uprotocol_zenoh_rust::{conformance, testing, ULinkZenoh};
+
+async fn ulink() -> Arc<ULinkZenoh> {
+    Arc::new(ULinkZenoh::from_session(
+        testing::loopback_session().await.unwrap(),
+    ))
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_publish_subscribe() {
+    conformance::publish_subscribe(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_notification() {
+    conformance::notification(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_unregister() {
+    conformance::unregister(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_attributes_round_trip() {
+    conformance::attributes_round_trip(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_payload_formats() {
+    conformance::payload_formats(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_priorities() {
+    conformance::priorities(ulink().await.as_ref()).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_rpc_success() {
+    conformance::rpc_success(&ulink().await).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_rpc_error() {
+    conformance::rpc_error(&ulink().await).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_rpc_timeout() {
+    conformance::rpc_timeout(&ulink().await).await;
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_run_all() {
+    conformance::run_all(&ulink().await).await;
+}
@@ -0,0 +1,155 @@
+//
+// Copyright (c) 2024 ZettaScale Technology
+//
+// This program and the accompanying materials are made available under the
+// terms of the Eclipse Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
+//
+#![cfg(feature = "gateway")]
+use axum::{
+    body::{Body, HttpBody},
+    http::{Request, StatusCode},
+};
+use std::sync::{Arc, Mutex};
+use std::time;
+use tower::ServiceExt;
+use uprotocol_sdk::{
+    transport::{builder::UAttributesBuilder, datamodel::UTransport},
+    uprotocol::{Data, UPayload, UPayloadFormat, UPriority},
+    uuid::builder::UUIDv8Builder,
+};
+use uprotocol_zenoh_rust::{cloudevent, gateway, testing, uri, ULinkZenoh};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_publish() {
+    let ulinkzenoh = Arc::new(ULinkZenoh::from_session(
+        testing::loopback_session().await.unwrap(),
+    ));
+    let uuri = uri::parse_uri("body.access@1234/1/5678").unwrap();
+
+    let received = Arc::new(Mutex::new(vec![]));
+    let received_cloned = received.clone();
+    ulinkzenoh
+        .register_listener(
+            uuri.clone(),
+            Box::new(move |result| received_cloned.lock().unwrap().push(result.unwrap())),
+        )
+        .await
+        .unwrap();
+
+    // Binary mode CloudEvent
+    let id = cloudevent::uuid_to_string(&UUIDv8Builder::new().build());
+    let request = Request::post("/events")
+        .header("ce-specversion", "1.0")
+        .header("ce-id", id)
+        .header("ce-type", "pub.v1")
+        .header("ce-source", "body.access@1234/1/5678")
+        .header("ce-priority", "UPRIORITY_CS1")
+        .header("content-type", "text/plain")
+        .body(Body::from("Hello World!"))
+        .unwrap();
+    let response = gateway::router(ulinkzenoh.clone())
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert!(
+        testing::wait_until(time::Duration::from_secs(5), || !received
+            .lock()
+            .unwrap()
+            .is_empty())
+        .await
+    );
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.len(), 1);
+    assert_eq!(
+        received[0].payload.as_ref().unwrap().data,
+        Some(Data::Value(b"Hello World!".to_vec()))
+    );
+
+    // Not a uProtocol CloudEvent
+    let request = Request::post("/events")
+        .header("ce-specversion", "1.0")
+        .header("ce-id", "1")
+        .header("ce-type", "com.example.event")
+        .header("ce-source", "body.access@1234/1/5678")
+        .body(Body::empty())
+        .unwrap();
+    let response = gateway::router(ulinkzenoh).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_invoke_timeout() {
+    let ulinkzenoh = Arc::new(ULinkZenoh::from_session(
+        testing::loopback_session().await.unwrap(),
+    ));
+
+    // Nobody serves the method, so the invoke fails with DeadlineExceeded
+    let request = Request::post("/events")
+        .header("ce-specversion", "1.0")
+        .header(
+            "ce-id",
+            cloudevent::uuid_to_string(&UUIDv8Builder::new().build()),
+        )
+        .header("ce-type", "req.v1")
+        .header("ce-source", "body.monitor@4321/1/0")
+        .header("ce-sink", "test_rpc.app@1234/1/5678")
+        .header("ce-priority", "UPRIORITY_CS4")
+        .header("ce-ttl", "200")
+        .header(
+            "ce-reqid",
+            cloudevent::uuid_to_string(&UUIDv8Builder::new().build()),
+        )
+        .header("content-type", "text/plain")
+        .body(Body::from("ping"))
+        .unwrap();
+    let response = gateway::router(ulinkzenoh).oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_gateway_subscribe() {
+    let ulinkzenoh = Arc::new(ULinkZenoh::from_session(
+        testing::loopback_session().await.unwrap(),
+    ));
+
+    let request = Request::get("/events?topic=1234/1/*")
+        .body(Body::empty())
+        .unwrap();
+    let response = gateway::router(ulinkzenoh.clone())
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    ulinkzenoh
+        .send(
+            uri::parse_uri("body.access@1234/1/5678").unwrap(),
+            payload,
+            attributes,
+        )
+        .await
+        .unwrap();
+
+    // The server-sent event holds the structured CloudEvent
+    let mut body = response.into_body();
+    let chunk = body.data().await.unwrap().unwrap();
+    let chunk = String::from_utf8(chunk.to_vec()).unwrap();
+    assert!(chunk.starts_with("event: cloudevent\n"));
+    assert!(chunk.contains("\"type\":\"pub.v1\""));
+    assert!(chunk.contains("\"source\":\"0100162e04d20100\""));
+}
@@ -11,22 +11,40 @@
 // Contributors:
 //   ZettaScale Zenoh Team, <zenoh@zettascale.tech>
 //
-use async_std::task::{self, block_on};
+use ed25519_dalek::SigningKey;
 use std::sync::{Arc, Mutex};
 use std::time;
 use uprotocol_sdk::{
-    rpc::{RpcClient, RpcServer},
+    rpc::{RpcClient, RpcMapperError, RpcServer},
     transport::builder::UAttributesBuilder,
     transport::datamodel::UTransport,
     uprotocol::{
-        Data, UCode, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat, UPriority,
-        UResource, UStatus, UUri,
+        Data, UAttributes, UCode, UEntity, UMessage, UMessageType, UPayload, UPayloadFormat,
+        UPriority, UResource, UStatus, UUri,
     },
     uri::builder::resourcebuilder::UResourceBuilder,
     uuid::builder::UUIDv8Builder,
 };
-use uprotocol_zenoh_rust::ULinkZenoh;
-use zenoh::config::Config;
+use uprotocol_zenoh_rust::{
+    acl::{AccessControl, AccessPolicy, Action},
+    blocking, cloudevent,
+    crypto::StaticKeys,
+    discovery::{UDiscoveryClient, UDiscoveryService},
+    liveliness::{AliveKind, LivelinessEvent},
+    rt::{self, block_on},
+    signing::{SignaturePolicy, SigningKeys, TrustStore},
+    storage::{self, Storage, StorageConfig},
+    testing::{self, loopback_session},
+    usubscription::{
+        self, NoPersistence, SubscriberInfo, SubscriptionPersistence, SubscriptionState,
+        USubscriptionClient, USubscriptionService,
+    },
+    ULinkZenoh,
+};
+use zenoh::prelude::r#async::*;
+
+// Upper bound of the waits, which return as soon as the condition holds
+const TIMEOUT: time::Duration = time::Duration::from_secs(5);
 
 // TODO: Need to check whether the way to create ID is correct?
 fn create_utransport_uuri() -> UUri {
@@ -64,9 +82,10 @@ fn create_rpcserver_uuri() -> UUri {
     }
 }
 
-#[async_std::test]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
 async fn test_utransport_register_and_unregister() {
-    let ulinkzenoh = ULinkZenoh::new(Config::default()).await.unwrap();
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap());
     let uuri = create_utransport_uuri();
 
     // Compare the return string
@@ -96,9 +115,10 @@ async fn test_utransport_register_and_unregister() {
     )
 }
 
-#[async_std::test]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
 async fn test_rpcserver_register_and_unregister() {
-    let ulinkzenoh = ULinkZenoh::new(Config::default()).await.unwrap();
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap());
     let uuri = create_rpcserver_uuri();
 
     // Compare the return string
@@ -128,21 +148,25 @@ async fn test_rpcserver_register_and_unregister() {
     )
 }
 
-#[async_std::test]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
 async fn test_publish_and_subscribe() {
     let target_data = String::from("Hello World!");
-    let ulinkzenoh = ULinkZenoh::new(Config::default()).await.unwrap();
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap());
     let uuri = create_utransport_uuri();
 
     // Register the listener
     let uuri_cloned = uuri.clone();
     let data_cloned = target_data.clone();
+    let received = Arc::new(Mutex::new(false));
+    let received_cloned = received.clone();
     let listener = move |result: Result<UMessage, UStatus>| match result {
         Ok(msg) => {
             if let Data::Value(v) = msg.payload.unwrap().data.unwrap() {
                 let value = v.into_iter().map(|c| c as char).collect::<String>();
                 assert_eq!(msg.source.unwrap(), uuri_cloned);
                 assert_eq!(value, data_cloned);
+                *received_cloned.lock().unwrap() = true;
             } else {
                 panic!("The message should be Data::Value type.");
             }
@@ -169,7 +193,7 @@ async fn test_publish_and_subscribe() {
         .unwrap();
 
     // Waiting for the subscriber to receive data
-    task::sleep(time::Duration::from_millis(1000)).await;
+    assert!(testing::wait_until(TIMEOUT, || *received.lock().unwrap()).await);
 
     // Cleanup
     ulinkzenoh
@@ -178,12 +202,12 @@ async fn test_publish_and_subscribe() {
         .unwrap();
 }
 
-#[async_std::test]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
 async fn test_rpc_server_client() {
-    let ulinkzenoh_client = ULinkZenoh::new(Config::default()).await.unwrap();
-    let ulinkzenoh_server = Arc::new(Mutex::new(
-        ULinkZenoh::new(Config::default()).await.unwrap(),
-    ));
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(Mutex::new(ULinkZenoh::from_session(session)));
     let client_data = String::from("This is the client data");
     let server_data = String::from("This is the server data");
     let uuri = create_rpcserver_uuri();
@@ -236,8 +260,6 @@ async fn test_rpc_server_client() {
         .register_rpc_listener(uuri.clone(), Box::new(callback))
         .await
         .unwrap();
-    // Need some time for queryable to run
-    task::sleep(time::Duration::from_millis(1000)).await;
 
     // Create uattributes
     // TODO: Check TTL (Should TTL map to Zenoh's timeout?)
@@ -263,3 +285,1332 @@ async fn test_rpc_server_client() {
         panic!("Failed to get result from invoke_method.");
     }
 }
+
+#[test]
+fn test_blocking_publish_and_subscribe() {
+    let target_data = String::from("Hello Blocking World!");
+    let ulinkzenoh = blocking::ULinkZenoh::new(testing::loopback_config()).unwrap();
+    let uuri = create_utransport_uuri();
+
+    // Subscribe and publish without any executor
+    let receiver = ulinkzenoh.subscribe(uuri.clone()).unwrap();
+    let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(target_data.as_bytes().to_vec())),
+    };
+    ulinkzenoh.send(uuri.clone(), payload, attributes).unwrap();
+
+    // Poll the received message
+    let msg = receiver
+        .recv_timeout(time::Duration::from_millis(1000))
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.source.unwrap(), uuri);
+    if let Data::Value(v) = msg.payload.unwrap().data.unwrap() {
+        assert_eq!(String::from_utf8(v).unwrap(), target_data);
+    } else {
+        panic!("The message should be Data::Value type.");
+    }
+    assert!(receiver.try_recv().is_none());
+}
+
+#[test]
+fn test_blocking_rpc() {
+    let ulinkzenoh = blocking::ULinkZenoh::new(testing::loopback_config()).unwrap();
+    let uuri = create_rpcserver_uuri();
+    let requests = ulinkzenoh.serve(uuri.clone()).unwrap();
+
+    std::thread::scope(|scope| {
+        // The server answers from its own thread, echoing the request
+        let server = &ulinkzenoh;
+        scope.spawn(move || {
+            let request = requests.recv_timeout(TIMEOUT).unwrap().unwrap();
+            let mut attributes = request.attributes.unwrap();
+            attributes.set_type(UMessageType::UmessageTypeResponse);
+            server
+                .send(
+                    request.source.unwrap(),
+                    request.payload.unwrap(),
+                    attributes,
+                )
+                .unwrap();
+        });
+
+        let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 1000)
+            .with_reqid(UUIDv8Builder::new().build())
+            .build();
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(b"Echo".to_vec())),
+        };
+        let response = ulinkzenoh.invoke_method(uuri, payload, attributes).unwrap();
+        assert_eq!(response.data, Some(Data::Value(b"Echo".to_vec())));
+    });
+}
+
+#[test]
+fn test_blocking_receiver_drop() {
+    let ulinkzenoh = blocking::ULinkZenoh::new(testing::loopback_config()).unwrap();
+    let uuri = create_utransport_uuri();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello".to_vec())),
+    };
+    let topic = uuri.clone();
+    let received = move |ulinkzenoh: &blocking::ULinkZenoh| {
+        ulinkzenoh
+            .as_async()
+            .topic_metrics(&topic)
+            .unwrap()
+            .map_or(0, |metrics| metrics.messages_received)
+    };
+
+    let receiver = ulinkzenoh.subscribe(uuri.clone()).unwrap();
+    ulinkzenoh
+        .send(
+            uuri.clone(),
+            payload.clone(),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .unwrap();
+    assert!(receiver.recv_timeout(TIMEOUT).is_some());
+    assert_eq!(received(&ulinkzenoh), 1);
+
+    // Dropping the receiver unregisters its listener, so nothing is delivered anymore
+    let listener = receiver.listener().to_string();
+    drop(receiver);
+    assert!(ulinkzenoh
+        .unregister_listener(uuri.clone(), &listener)
+        .is_err());
+    ulinkzenoh
+        .send(
+            uuri,
+            payload,
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .unwrap();
+    std::thread::sleep(time::Duration::from_millis(200));
+    assert_eq!(received(&ulinkzenoh), 1);
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_wait_for_listener_and_rpc_server() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh = ULinkZenoh::from_session(session.clone());
+    let topic = create_utransport_uuri();
+    let method = create_rpcserver_uuri();
+    let short_timeout = time::Duration::from_millis(100);
+
+    // Nothing is registered yet
+    let status = ulinkzenoh
+        .wait_for_listener(&topic, short_timeout)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::DeadlineExceeded as i32);
+    let status = ulinkzenoh
+        .wait_for_rpc_server(&method, short_timeout)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::DeadlineExceeded as i32);
+
+    // Already registered
+    ulinkzenoh
+        .register_listener(topic.clone(), Box::new(|_| {}))
+        .await
+        .unwrap();
+    ulinkzenoh.wait_for_listener(&topic, TIMEOUT).await.unwrap();
+
+    // Registered while waiting
+    let server = ULinkZenoh::from_session(session);
+    let method_cloned = method.clone();
+    rt::spawn(async move {
+        rt::sleep(short_timeout).await;
+        server
+            .register_rpc_listener(method_cloned, Box::new(|_| {}))
+            .await
+            .unwrap();
+        // Keep the server alive until the end of the test
+        rt::sleep(TIMEOUT).await;
+    });
+    ulinkzenoh
+        .wait_for_rpc_server(&method, TIMEOUT)
+        .await
+        .unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_pattern_listener_liveliness() {
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap());
+    let topic = create_utransport_uuri();
+
+    // A pattern listener matching the topic doesn't make it look listened
+    let pattern = UUri {
+        entity: Some(UEntity {
+            id: Some(1234),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let listener_string = ulinkzenoh
+        .register_pattern_listener(&pattern, Box::new(|_| {}))
+        .await
+        .unwrap();
+    assert!(!ulinkzenoh.is_alive(AliveKind::Topic, &topic).await.unwrap());
+
+    // Unlike a listener of the topic
+    ulinkzenoh
+        .register_listener(topic.clone(), Box::new(|_| {}))
+        .await
+        .unwrap();
+    assert!(ulinkzenoh.is_alive(AliveKind::Topic, &topic).await.unwrap());
+    ulinkzenoh
+        .unregister_pattern_listener(&listener_string)
+        .unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_rpc_server_liveliness() {
+    let (client_session, server_session) = testing::connected_sessions().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(client_session);
+    let ulinkzenoh_server = ULinkZenoh::from_session(server_session);
+    let uuri = create_rpcserver_uuri();
+
+    // Watch the methods and wait for the sessions to discover each other
+    let watcher = ulinkzenoh_client
+        .watch_liveliness(Some(AliveKind::Method))
+        .await
+        .unwrap();
+    testing::wait_for_peer(&ulinkzenoh_client, TIMEOUT)
+        .await
+        .unwrap();
+
+    // The method is alive once the listener is registered
+    let listener_string = ulinkzenoh_server
+        .register_rpc_listener(uuri.clone(), Box::new(|_| {}))
+        .await
+        .unwrap();
+    let Some(LivelinessEvent::Appeared(resource)) = watcher.recv_async().await else {
+        panic!("The method should appear");
+    };
+    assert_eq!(resource.kind, AliveKind::Method);
+    assert_eq!(resource.uri.entity.unwrap().id, Some(1234));
+    assert!(ulinkzenoh_client
+        .is_alive(AliveKind::Method, &uuri)
+        .await
+        .unwrap());
+
+    // And disappears once unregistered
+    ulinkzenoh_server
+        .unregister_rpc_listener(uuri.clone(), &listener_string)
+        .await
+        .unwrap();
+    let Some(LivelinessEvent::Disappeared(resource)) = watcher.recv_async().await else {
+        panic!("The method should disappear");
+    };
+    assert_eq!(resource.kind, AliveKind::Method);
+    assert!(!ulinkzenoh_client
+        .is_alive(AliveKind::Method, &uuri)
+        .await
+        .unwrap());
+}
+
+#[cfg(feature = "tracing")]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_trace_propagation() {
+    use uprotocol_zenoh_rust::trace::{self, TraceContext};
+
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = ULinkZenoh::from_session(session);
+    let uuri = create_rpcserver_uuri();
+
+    // The server keeps the request and the trace its listener runs in
+    let seen = Arc::new(Mutex::new(None));
+    let seen_cloned = seen.clone();
+    ulinkzenoh_server
+        .register_rpc_listener(
+            uuri.clone(),
+            Box::new(move |result| {
+                if let Ok(msg) = result {
+                    *seen_cloned.lock().unwrap() = Some((msg, TraceContext::current()));
+                }
+            }),
+        )
+        .await
+        .unwrap();
+
+    // The client invokes within its own trace, the response doesn't matter
+    let caller =
+        TraceContext::from_traceparent("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01")
+            .unwrap();
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 100)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    let _ = trace::with_context(
+        caller,
+        ulinkzenoh_client.invoke_method_message(uuri, payload, attributes),
+    )
+    .await;
+    assert!(testing::wait_until(TIMEOUT, || seen.lock().unwrap().is_some()).await);
+
+    // The server continues the trace of the client, the request being the parent
+    let (msg, ctx) = seen.lock().unwrap().take().unwrap();
+    let id = msg.attributes.unwrap().id.unwrap();
+    assert_eq!(
+        ctx,
+        Some(TraceContext {
+            trace_id: caller.trace_id,
+            parent_id: id.lsb,
+            sampled: true,
+        })
+    );
+    assert_eq!(TraceContext::current(), None);
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_udiscovery_lookup_uri() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(ULinkZenoh::from_session(session));
+
+    // The server hosts body.access
+    let service = UDiscoveryService::start(&ulinkzenoh_server, None)
+        .await
+        .unwrap();
+    let entity = create_utransport_uuri().entity.unwrap();
+    service.host(entity.clone()).await.unwrap();
+
+    // The client resolves it by name
+    let client = UDiscoveryClient::new(&ulinkzenoh_client);
+    let uris = client.lookup_uri("body.access", None).await.unwrap();
+    assert_eq!(uris.len(), 1);
+    assert_eq!(uris[0].entity, Some(entity.clone()));
+    assert_eq!(
+        client.resolve("body.access").await.unwrap().entity,
+        Some(entity.clone())
+    );
+
+    // Unknown names and versions aren't found
+    let result = client.lookup_uri("body.access", Some(2)).await;
+    assert_eq!(result.unwrap_err().code, UCode::NotFound as i32);
+    let result = client.lookup_uri("unknown.app", None).await;
+    assert_eq!(result.unwrap_err().code, UCode::NotFound as i32);
+
+    // The hosted uEntities don't look alive once the service is stopped
+    let uri = UUri {
+        entity: Some(entity),
+        ..Default::default()
+    };
+    assert!(ulinkzenoh_client
+        .is_alive(AliveKind::Entity, &uri)
+        .await
+        .unwrap());
+    service.stop().await.unwrap();
+    assert!(!ulinkzenoh_client
+        .is_alive(AliveKind::Entity, &uri)
+        .await
+        .unwrap());
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_usubscription() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(ULinkZenoh::from_session(session));
+    let uuri = create_utransport_uuri();
+
+    let service = USubscriptionService::start(&ulinkzenoh_server, Box::new(NoPersistence))
+        .await
+        .unwrap();
+    let updates = Arc::new(Mutex::new(vec![]));
+    let updates_cloned = updates.clone();
+    let update_listener = usubscription::register_update_listener(
+        &ulinkzenoh_server,
+        Box::new(move |result| {
+            updates_cloned.lock().unwrap().push(result.unwrap());
+        }),
+    )
+    .await
+    .unwrap();
+
+    // Subscribe through uSubscription
+    let subscriber = UUri {
+        entity: Some(UEntity {
+            name: "dashboard.app".to_string(),
+            version_major: Some(1),
+            id: Some(42),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    let client = USubscriptionClient::new(&ulinkzenoh_client, subscriber.clone());
+    let listener = client
+        .subscribe(uuri.clone(), Box::new(|_| {}))
+        .await
+        .unwrap();
+    assert_eq!(
+        client.fetch_subscribers(uuri.clone()).await.unwrap(),
+        vec![subscriber.clone()]
+    );
+
+    // Unsubscribe
+    client.unsubscribe(uuri.clone(), &listener).await.unwrap();
+    assert!(client
+        .fetch_subscribers(uuri.clone())
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(testing::wait_until(TIMEOUT, || updates.lock().unwrap().len() == 2).await);
+
+    // The producer side is notified of both changes
+    {
+        let updates = updates.lock().unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(
+            updates[0].subscriber.as_ref().unwrap().uri,
+            Some(subscriber)
+        );
+        assert_eq!(
+            updates[1].status.as_ref().unwrap().state,
+            SubscriptionState::Unsubscribed as i32
+        );
+    }
+
+    ulinkzenoh_server
+        .unregister_listener(usubscription::notification_topic(), &update_listener)
+        .await
+        .unwrap();
+    service.stop().await.unwrap();
+}
+
+// Persistence whose writes all fail
+struct FullPersistence;
+
+impl SubscriptionPersistence for FullPersistence {
+    fn load(&self) -> Result<Vec<(UUri, SubscriberInfo)>, UStatus> {
+        Ok(vec![])
+    }
+
+    fn save(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+        Err(UStatus::fail_with_code(
+            UCode::ResourceExhausted,
+            "Disk full",
+        ))
+    }
+
+    fn remove(&self, _topic: &UUri, _subscriber: &SubscriberInfo) -> Result<(), UStatus> {
+        Err(UStatus::fail_with_code(
+            UCode::ResourceExhausted,
+            "Disk full",
+        ))
+    }
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_usubscription_failure() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(ULinkZenoh::from_session(session));
+    let uuri = create_utransport_uuri();
+    let client = USubscriptionClient::new(&ulinkzenoh_client, create_rpcserver_uuri());
+
+    // Without the service, the call times out
+    let status = client
+        .subscribe(uuri.clone(), Box::new(|_| {}))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::DeadlineExceeded as i32);
+
+    // The code of the service is kept
+    let service = USubscriptionService::start(&ulinkzenoh_server, Box::new(FullPersistence))
+        .await
+        .unwrap();
+    let status = client
+        .subscribe(uuri.clone(), Box::new(|_| {}))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::ResourceExhausted as i32);
+    assert!(client.fetch_subscribers(uuri).await.unwrap().is_empty());
+
+    service.stop().await.unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_publication_cache() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_publisher = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_subscriber = ULinkZenoh::from_session(session);
+    let uuri = create_utransport_uuri();
+
+    // Publish before anyone listens, only the last 2 are kept
+    ulinkzenoh_publisher
+        .enable_publication_cache(&uuri, 2)
+        .await
+        .unwrap();
+    for data in ["first", "second", "third"] {
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(data.as_bytes().to_vec())),
+        };
+        let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+        ulinkzenoh_publisher
+            .send(uuri.clone(), payload, attributes)
+            .await
+            .unwrap();
+    }
+
+    // The late joiner gets them, with their uattributes
+    let received = Arc::new(Mutex::new(vec![]));
+    let received_cloned = received.clone();
+    let listener_string = ulinkzenoh_subscriber
+        .register_querying_listener(
+            uuri.clone(),
+            Box::new(move |result: Result<UMessage, UStatus>| {
+                let msg = result.unwrap();
+                assert!(msg.attributes.unwrap().id.is_some());
+                if let Data::Value(v) = msg.payload.unwrap().data.unwrap() {
+                    received_cloned
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8(v).unwrap());
+                }
+            }),
+        )
+        .await
+        .unwrap();
+    assert_eq!(*received.lock().unwrap(), vec!["second", "third"]);
+
+    // Cleanup
+    ulinkzenoh_subscriber
+        .unregister_listener(uuri.clone(), &listener_string)
+        .await
+        .unwrap();
+    ulinkzenoh_publisher
+        .disable_publication_cache(&uuri)
+        .unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_get_latest() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_publisher = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_client = ULinkZenoh::from_session(session);
+    let uuri = create_utransport_uuri();
+
+    // Nothing is kept yet
+    let result = ulinkzenoh_client.get_latest(uuri.clone()).await;
+    assert_eq!(result.unwrap_err().code, UCode::NotFound as i32);
+
+    ulinkzenoh_publisher
+        .enable_publication_cache(&uuri, 1)
+        .await
+        .unwrap();
+    for data in ["old", "latest"] {
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(data.as_bytes().to_vec())),
+        };
+        let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+        ulinkzenoh_publisher
+            .send(uuri.clone(), payload, attributes)
+            .await
+            .unwrap();
+    }
+
+    // The latest message is rebuilt with its format and uattributes
+    let msg = ulinkzenoh_client.get_latest(uuri.clone()).await.unwrap();
+    assert_eq!(msg.source, Some(uuri.clone()));
+    assert!(msg.attributes.unwrap().id.is_some());
+    let payload = msg.payload.unwrap();
+    assert_eq!(payload.format, UPayloadFormat::UpayloadFormatText as i32);
+    assert_eq!(payload.data, Some(Data::Value(b"latest".to_vec())));
+
+    ulinkzenoh_publisher
+        .disable_publication_cache(&uuri)
+        .unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_storage() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_storage = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_client = ULinkZenoh::from_session(session);
+    let uuri = create_utransport_uuri();
+    let path = std::env::temp_dir().join(format!("uprotocol_storage_{}", std::process::id()));
+
+    // Store every topic of the uEntity
+    let mut pattern = uuri.clone();
+    pattern.resource = None;
+    let storage = Storage::start(
+        &ulinkzenoh_storage,
+        StorageConfig::new(&path).topic(pattern.clone()),
+    )
+    .await
+    .unwrap();
+
+    for data in ["first", "second"] {
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(data.as_bytes().to_vec())),
+        };
+        let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+        ulinkzenoh_client
+            .send(uuri.clone(), payload, attributes)
+            .await
+            .unwrap();
+    }
+    assert!(testing::wait_until(TIMEOUT, || storage.size() > 0).await);
+
+    // Everything stored, in order
+    let messages = storage::query(&ulinkzenoh_client, &pattern, None, None)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].source, Some(uuri.clone()));
+    assert_eq!(
+        messages[1].payload.as_ref().unwrap().data,
+        Some(Data::Value(b"second".to_vec()))
+    );
+
+    // Nothing stored in the future
+    let start = time::SystemTime::now() + time::Duration::from_secs(3600);
+    let messages = storage::query(&ulinkzenoh_client, &pattern, Some(start), None)
+        .await
+        .unwrap();
+    assert!(messages.is_empty());
+
+    // The storage answers get_latest
+    let msg = ulinkzenoh_client.get_latest(uuri.clone()).await.unwrap();
+    assert_eq!(
+        msg.payload.unwrap().data,
+        Some(Data::Value(b"second".to_vec()))
+    );
+
+    drop(storage);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_encryption() {
+    let session = loopback_session().await.unwrap();
+    let uuri = create_utransport_uuri();
+    let mut keys = StaticKeys::new();
+    keys.insert(&uuri, [1; 32]).unwrap();
+    let keys = Arc::new(keys);
+    let mut other_keys = StaticKeys::new();
+    other_keys.insert(&uuri, [2; 32]).unwrap();
+    let ulinkzenoh_publisher =
+        ULinkZenoh::from_session(session.clone()).with_encryption(keys.clone());
+    let ulinkzenoh_subscriber = ULinkZenoh::from_session(session.clone()).with_encryption(keys);
+    let ulinkzenoh_intruder =
+        ULinkZenoh::from_session(session.clone()).with_encryption(Arc::new(other_keys));
+    let ulinkzenoh_router = ULinkZenoh::from_session(session);
+
+    let results = Arc::new(Mutex::new(vec![]));
+    for ulinkzenoh in [
+        &ulinkzenoh_subscriber,
+        &ulinkzenoh_intruder,
+        &ulinkzenoh_router,
+    ] {
+        let results_cloned = results.clone();
+        ulinkzenoh
+            .register_listener(
+                uuri.clone(),
+                Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+            )
+            .await
+            .unwrap();
+    }
+
+    let attributes = UAttributes {
+        token: Some("secret".to_string()),
+        ..UAttributesBuilder::publish(UPriority::UpriorityCs4).build()
+    };
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    ulinkzenoh_publisher
+        .send(uuri, payload, attributes)
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || results.lock().unwrap().len() == 3).await);
+
+    let results = results.lock().unwrap();
+    // The subscriber with the key decrypts it
+    let msg = results
+        .iter()
+        .find_map(|result| {
+            result
+                .as_ref()
+                .ok()
+                .filter(|msg| msg.attributes.as_ref().unwrap().token.is_some())
+        })
+        .expect("The message should be decrypted");
+    assert_eq!(
+        msg.payload.as_ref().unwrap().data,
+        Some(Data::Value(b"Hello World!".to_vec()))
+    );
+    // The one with another key is denied
+    assert!(results.iter().any(|result| result
+        .as_ref()
+        .is_err_and(|status| status.code == UCode::PermissionDenied as i32)));
+    // And the one without key only sees the ciphertext
+    assert!(results
+        .iter()
+        .any(|result| result.as_ref().is_ok_and(|msg| {
+            msg.attributes.as_ref().unwrap().token.is_none()
+                && msg.payload.as_ref().unwrap().data != Some(Data::Value(b"Hello World!".to_vec()))
+        })));
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_storage_encryption() {
+    let session = loopback_session().await.unwrap();
+    let uuri = create_utransport_uuri();
+    let mut keys = StaticKeys::new();
+    keys.insert(&uuri, [1; 32]).unwrap();
+    let mut other_keys = StaticKeys::new();
+    other_keys.insert(&uuri, [2; 32]).unwrap();
+    // The storage only sees ciphertext
+    let ulinkzenoh_storage = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_client =
+        ULinkZenoh::from_session(session.clone()).with_encryption(Arc::new(keys));
+    let ulinkzenoh_intruder =
+        ULinkZenoh::from_session(session).with_encryption(Arc::new(other_keys));
+    let path = std::env::temp_dir().join(format!(
+        "uprotocol_storage_encryption_{}",
+        std::process::id()
+    ));
+    let storage = Storage::start(
+        &ulinkzenoh_storage,
+        StorageConfig::new(&path).topic(uuri.clone()),
+    )
+    .await
+    .unwrap();
+
+    let attributes = UAttributes {
+        token: Some("secret".to_string()),
+        ..UAttributesBuilder::publish(UPriority::UpriorityCs4).build()
+    };
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    ulinkzenoh_client
+        .send(uuri.clone(), payload.clone(), attributes)
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || storage.size() > 0).await);
+
+    // The reader with the key gets the plaintext back
+    let messages = storage::query(&ulinkzenoh_client, &uuri, None, None)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].payload, Some(payload));
+    assert_eq!(
+        messages[0].attributes.as_ref().unwrap().token,
+        Some("secret".to_string())
+    );
+    // And the one with another key is denied
+    let status = storage::query(&ulinkzenoh_intruder, &uuri, None, None)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+    drop(storage);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_signing() {
+    let session = loopback_session().await.unwrap();
+    let uuri = create_utransport_uuri();
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut trust_store = TrustStore::new(SignaturePolicy::Require);
+    trust_store
+        .trust(uuri.entity.as_ref().unwrap(), key.verifying_key())
+        .unwrap();
+    let mut keys = SigningKeys::new();
+    keys.insert(uuri.entity.as_ref().unwrap(), key).unwrap();
+    let ulinkzenoh_publisher =
+        ULinkZenoh::from_session(session.clone()).with_signing(Arc::new(keys));
+    let ulinkzenoh_impostor = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_subscriber =
+        ULinkZenoh::from_session(session).with_trust_store(Arc::new(trust_store));
+
+    let results = Arc::new(Mutex::new(vec![]));
+    let results_cloned = results.clone();
+    ulinkzenoh_subscriber
+        .register_listener(
+            uuri.clone(),
+            Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+
+    let payload = |data: &[u8]| UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(data.to_vec())),
+    };
+    let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+    ulinkzenoh_publisher
+        .send(uuri.clone(), payload(b"Signed"), attributes.clone())
+        .await
+        .unwrap();
+    ulinkzenoh_impostor
+        .send(uuri, payload(b"Unsigned"), attributes)
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || results.lock().unwrap().len() == 2).await);
+
+    let results = results.lock().unwrap();
+    // The signed message is delivered
+    assert!(results.iter().any(|result| result
+        .as_ref()
+        .is_ok_and(|msg| msg.payload == Some(payload(b"Signed")))));
+    // And the unsigned one rejected
+    assert!(results.iter().any(|result| result
+        .as_ref()
+        .is_err_and(|status| status.code == UCode::Unauthenticated as i32)));
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_signing_requests() {
+    let session = loopback_session().await.unwrap();
+    let method = create_rpcserver_uuri();
+    let client = UEntity {
+        name: "body.monitor".to_string(),
+        version_major: Some(1),
+        id: Some(4321),
+        ..Default::default()
+    };
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut trust_store = TrustStore::new(SignaturePolicy::Require);
+    trust_store.trust(&client, key.verifying_key()).unwrap();
+    let mut keys = SigningKeys::new();
+    keys.insert(&client, key).unwrap();
+    keys.set_requester(&client).unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone()).with_signing(Arc::new(keys));
+    let ulinkzenoh_forger = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server =
+        ULinkZenoh::from_session(session).with_trust_store(Arc::new(trust_store));
+
+    let results = Arc::new(Mutex::new(vec![]));
+    let results_cloned = results.clone();
+    ulinkzenoh_server
+        .register_rpc_listener(
+            method.clone(),
+            Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+
+    // Nobody responds, only what the server gets matters
+    for ulinkzenoh in [&ulinkzenoh_client, &ulinkzenoh_forger] {
+        let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 100)
+            .with_reqid(UUIDv8Builder::new().build())
+            .build();
+        let payload = UPayload {
+            length: Some(0),
+            format: UPayloadFormat::UpayloadFormatText as i32,
+            data: Some(Data::Value(b"ping".to_vec())),
+        };
+        let _ = ulinkzenoh
+            .invoke_method(method.clone(), payload, attributes)
+            .await;
+    }
+    assert!(testing::wait_until(TIMEOUT, || results.lock().unwrap().len() == 2).await);
+
+    let results = results.lock().unwrap();
+    // The signed request reaches the listener, and the forged one is rejected
+    assert!(results[0].is_ok());
+    assert_eq!(
+        results[1].as_ref().unwrap_err().code,
+        UCode::Unauthenticated as i32
+    );
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_storage_signing() {
+    let session = loopback_session().await.unwrap();
+    let uuri = create_utransport_uuri();
+    let key = SigningKey::from_bytes(&[1; 32]);
+    let mut trust_store = TrustStore::new(SignaturePolicy::Require);
+    trust_store
+        .trust(uuri.entity.as_ref().unwrap(), key.verifying_key())
+        .unwrap();
+    let mut keys = SigningKeys::new();
+    keys.insert(uuri.entity.as_ref().unwrap(), key).unwrap();
+    let ulinkzenoh_publisher =
+        ULinkZenoh::from_session(session.clone()).with_signing(Arc::new(keys));
+    let ulinkzenoh_storage = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_reader =
+        ULinkZenoh::from_session(session).with_trust_store(Arc::new(trust_store));
+    let path =
+        std::env::temp_dir().join(format!("uprotocol_storage_signing_{}", std::process::id()));
+    let storage = Storage::start(
+        &ulinkzenoh_storage,
+        StorageConfig::new(&path).topic(uuri.clone()),
+    )
+    .await
+    .unwrap();
+
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Signed".to_vec())),
+    };
+    ulinkzenoh_publisher
+        .send(
+            uuri.clone(),
+            payload.clone(),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || storage.size() > 0).await);
+
+    // The stored message is still signed, whichever way it's read
+    let messages = storage::query(&ulinkzenoh_reader, &uuri, None, None)
+        .await
+        .unwrap();
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].payload, Some(payload.clone()));
+    let msg = ulinkzenoh_reader.get_latest(uuri).await.unwrap();
+    assert_eq!(msg.payload, Some(payload));
+
+    drop(storage);
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_expired_ttl() {
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh = ULinkZenoh::from_session(session);
+    let uuri = create_utransport_uuri();
+    let method = create_rpcserver_uuri();
+    // An id created 10s ago, expired for a TTL of 1s
+    let expired_id = || {
+        let mut id = UUIDv8Builder::new().build();
+        id.msb -= 10_000 << 16;
+        id
+    };
+
+    let results = Arc::new(Mutex::new(vec![]));
+    let results_cloned = results.clone();
+    ulinkzenoh
+        .register_listener(
+            uuri.clone(),
+            Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+    let results_cloned = results.clone();
+    ulinkzenoh
+        .register_rpc_listener(
+            method.clone(),
+            Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"late".to_vec())),
+    };
+    let attributes = UAttributes {
+        id: Some(expired_id()),
+        ttl: Some(1000),
+        ..UAttributesBuilder::publish(UPriority::UpriorityCs4).build()
+    };
+    ulinkzenoh
+        .send(uuri, payload.clone(), attributes)
+        .await
+        .unwrap();
+
+    // The caller of an expired request gets the DeadlineExceeded commstatus
+    let attributes = UAttributes {
+        id: Some(expired_id()),
+        ..UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 1000)
+            .with_reqid(UUIDv8Builder::new().build())
+            .build()
+    };
+    let response = ulinkzenoh
+        .invoke_method_message(method, payload, attributes)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.attributes.unwrap().commstatus,
+        Some(UCode::DeadlineExceeded as i32)
+    );
+
+    // And both listeners are told what they missed
+    assert!(testing::wait_until(TIMEOUT, || results.lock().unwrap().len() == 2).await);
+    assert!(results.lock().unwrap().iter().all(|result| result
+        .as_ref()
+        .is_err_and(|status| status.code == UCode::DeadlineExceeded as i32)));
+    assert_eq!(ulinkzenoh.metrics().ttl_drops, 2);
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_access_control() {
+    let policy = AccessPolicy::from_json(
+        r#"{ "entities": [{ "entity": 4321, "publish": ["4321/1/*"], "subscribe": ["1234/1/5678"] }] }"#,
+    )
+    .unwrap();
+    let entity = UEntity {
+        name: "body.monitor".to_string(),
+        version_major: Some(1),
+        id: Some(4321),
+        ..Default::default()
+    };
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_cloned = events.clone();
+    let access = AccessControl::new(policy, &entity)
+        .unwrap()
+        .with_audit(Box::new(move |event| {
+            events_cloned.lock().unwrap().push(event.clone());
+        }));
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap())
+        .with_access_control(Arc::new(access));
+    let uuri = create_utransport_uuri();
+
+    // Subscribing is granted, but not publishing to the topic of another uEntity
+    ulinkzenoh
+        .register_listener(uuri.clone(), Box::new(|_| {}))
+        .await
+        .unwrap();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    let status = ulinkzenoh
+        .send(
+            uuri.clone(),
+            payload.clone(),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    // Even with a payload which couldn't be sent anyway
+    let status = ulinkzenoh
+        .send(
+            uuri.clone(),
+            UPayload::default(),
+            UAttributesBuilder::publish(UPriority::UpriorityCs4).build(),
+        )
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    let status = ulinkzenoh
+        .enable_publication_cache(&uuri, 1)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+    // Nor reading the kept messages of the other topics
+    let mut other = uuri.clone();
+    other.resource.as_mut().unwrap().id = Some(5679);
+    let status = ulinkzenoh.get_latest(other).await.unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    let mut pattern = uuri;
+    pattern.resource = None;
+    let status = storage::query(&ulinkzenoh, &pattern, None, None)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    let path =
+        std::env::temp_dir().join(format!("uprotocol_storage_access_{}", std::process::id()));
+    let Err(status) = Storage::start(&ulinkzenoh, StorageConfig::new(&path).topic(pattern)).await
+    else {
+        panic!("The storage shouldn't start");
+    };
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+
+    // Nor serving or invoking any method
+    let method = create_rpcserver_uuri();
+    let status = ulinkzenoh
+        .register_rpc_listener(method.clone(), Box::new(|_| {}))
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, method.clone(), 100)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let status = ulinkzenoh
+        .invoke_method_message(method.clone(), payload.clone(), attributes.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::PermissionDenied as i32);
+    // Through RpcClient, the UStatus is only in the text of the error
+    let Err(RpcMapperError::UnexpectedError(message)) =
+        ulinkzenoh.invoke_method(method, payload, attributes).await
+    else {
+        panic!("The invocation should be denied");
+    };
+    assert!(message.contains(&format!("code: {}", UCode::PermissionDenied as i32)));
+
+    let events = events.lock().unwrap();
+    assert_eq!(
+        events
+            .iter()
+            .map(|event| (event.action, event.allowed))
+            .collect::<Vec<_>>(),
+        vec![
+            (Action::Subscribe, true),
+            (Action::Publish, false),
+            (Action::Publish, false),
+            (Action::Publish, false),
+            (Action::Subscribe, false),
+            (Action::Subscribe, false),
+            (Action::Subscribe, false),
+            (Action::Serve, false),
+            (Action::Invoke, false),
+            (Action::Invoke, false),
+        ]
+    );
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_foreign_publishers() {
+    let session = loopback_session().await.unwrap();
+    let uuri = create_utransport_uuri();
+    let ulinkzenoh_foreign = ULinkZenoh::from_session(session.clone()).with_foreign_publishers();
+    let ulinkzenoh_strict = ULinkZenoh::from_session(session.clone());
+
+    let results = Arc::new(Mutex::new(vec![]));
+    let errors = Arc::new(Mutex::new(vec![]));
+    let results_cloned = results.clone();
+    ulinkzenoh_foreign
+        .register_listener(
+            uuri.clone(),
+            Box::new(move |result| results_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+    let errors_cloned = errors.clone();
+    ulinkzenoh_strict
+        .register_listener(
+            uuri.clone(),
+            Box::new(move |result| errors_cloned.lock().unwrap().push(result)),
+        )
+        .await
+        .unwrap();
+
+    // A plain Zenoh publisher, on the Zenoh key of the topic
+    session
+        .put("0100162e04d20100", "Hello World!")
+        .priority(Priority::DataHigh)
+        .res()
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || results.lock().unwrap().len() == 1).await);
+    assert!(testing::wait_until(TIMEOUT, || errors.lock().unwrap().len() == 1).await);
+
+    let results = results.lock().unwrap();
+    let msg = results[0].as_ref().unwrap();
+    assert_eq!(msg.source, Some(uuri));
+    let attributes = msg.attributes.as_ref().unwrap();
+    assert_eq!(attributes.r#type, UMessageType::UmessageTypePublish as i32);
+    assert_eq!(attributes.priority, UPriority::UpriorityCs3 as i32);
+    assert!(attributes.id.is_some());
+    assert_eq!(
+        msg.payload.as_ref().unwrap().data,
+        Some(Data::Value(b"Hello World!".to_vec()))
+    );
+    // Without the mode, the sample is still rejected
+    assert!(errors.lock().unwrap()[0].is_err());
+}
+
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_cloudevent_listener() {
+    use cloudevents::AttributesReader;
+
+    let ulinkzenoh = ULinkZenoh::from_session(loopback_session().await.unwrap());
+    let uuri = create_utransport_uuri();
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_cloned = events.clone();
+    let listener = ulinkzenoh
+        .register_cloudevent_listener(
+            uuri.clone(),
+            Box::new(move |result| events_cloned.lock().unwrap().push(result.unwrap())),
+        )
+        .await
+        .unwrap();
+
+    let attributes = UAttributesBuilder::publish(UPriority::UpriorityCs4).build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"Hello World!".to_vec())),
+    };
+    ulinkzenoh
+        .send(uuri.clone(), payload, attributes)
+        .await
+        .unwrap();
+    assert!(testing::wait_until(TIMEOUT, || !events.lock().unwrap().is_empty()).await);
+
+    {
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].ty(), "pub.v1");
+        assert_eq!(events[0].datacontenttype(), Some("text/plain"));
+        // The UUri keeps its ids
+        assert_eq!(events[0].source().to_string(), "body.access@1234/1/5678");
+        let msg = cloudevent::cloudevent_to_umessage(&events[0]).unwrap();
+        assert_eq!(
+            msg.payload.unwrap().data,
+            Some(Data::Value(b"Hello World!".to_vec()))
+        );
+    }
+
+    ulinkzenoh
+        .unregister_listener(uuri, &listener)
+        .await
+        .unwrap();
+}
+
+#[cfg(feature = "tools")]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_mock_server() {
+    use uprotocol_zenoh_rust::mock::{MockConfig, MockServer};
+
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(ULinkZenoh::from_session(session));
+    let config = MockConfig::from_json(
+        r#"{"methods": [
+            {"method": "test_rpc.app@1234/1/5678", "response": {"text": "pong"}, "delay_ms": 100},
+            {"method": "test_rpc.app@1234/1/5679", "error": {"code": "unavailable"}}
+        ]}"#,
+    )
+    .unwrap();
+    let server = MockServer::start(&ulinkzenoh_server, config).await.unwrap();
+
+    // Canned response
+    let uuri = create_rpcserver_uuri();
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 1000)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"ping".to_vec())),
+    };
+    let response = ulinkzenoh_client
+        .invoke_method_message(uuri.clone(), payload.clone(), attributes)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload.unwrap().data,
+        Some(Data::Value(b"pong".to_vec()))
+    );
+    assert_eq!(response.attributes.unwrap().commstatus, None);
+
+    // Canned error
+    let mut uuri = uuri;
+    uuri.resource = Some(UResourceBuilder::for_rpc_request(None, Some(5679)));
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 1000)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let response = ulinkzenoh_client
+        .invoke_method_message(uuri, payload, attributes)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.attributes.unwrap().commstatus,
+        Some(UCode::Unavailable as i32)
+    );
+
+    server.stop().await.unwrap();
+}
+
+#[cfg(feature = "tools")]
+#[cfg_attr(feature = "tokio", tokio::test(flavor = "multi_thread"))]
+#[cfg_attr(not(feature = "tokio"), async_std::test)]
+async fn test_mock_server_slower_than_default_timeout() {
+    use uprotocol_zenoh_rust::mock::{MockConfig, MockServer};
+
+    let session = loopback_session().await.unwrap();
+    let ulinkzenoh_client = ULinkZenoh::from_session(session.clone());
+    let ulinkzenoh_server = Arc::new(ULinkZenoh::from_session(session));
+    let config = MockConfig::from_json(
+        r#"{"methods": [
+            {"method": "test_rpc.app@1234/1/5678", "response": {"text": "pong"}, "delay_ms": 1500}
+        ]}"#,
+    )
+    .unwrap();
+    let server = MockServer::start(&ulinkzenoh_server, config).await.unwrap();
+
+    // The response comes within the TTL of the request
+    let uuri = create_rpcserver_uuri();
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 3000)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let payload = UPayload {
+        length: Some(0),
+        format: UPayloadFormat::UpayloadFormatText as i32,
+        data: Some(Data::Value(b"ping".to_vec())),
+    };
+    let response = ulinkzenoh_client
+        .invoke_method_message(uuri.clone(), payload.clone(), attributes)
+        .await
+        .unwrap();
+    assert_eq!(
+        response.payload.unwrap().data,
+        Some(Data::Value(b"pong".to_vec()))
+    );
+
+    // But not after it
+    let attributes = UAttributesBuilder::request(UPriority::UpriorityCs4, uuri.clone(), 500)
+        .with_reqid(UUIDv8Builder::new().build())
+        .build();
+    let status = ulinkzenoh_client
+        .invoke_method_message(uuri, payload, attributes)
+        .await
+        .unwrap_err();
+    assert_eq!(status.code, UCode::DeadlineExceeded as i32);
+
+    server.stop().await.unwrap();
+}